@@ -0,0 +1,299 @@
+use crate::{parse_operand, Executable, Op, ParseError};
+use std::collections::HashMap;
+use std::fmt;
+
+/// Operand as written in the source, labels are resolved once every line has been read
+enum Operand<'a> {
+    Value(i32),
+    Label(&'a str),
+}
+
+struct Line<'a> {
+    number: usize,
+    text: &'a str,
+    mnemonic: &'a str,
+    operand: Operand<'a>,
+}
+
+/// Assembles source text into an executable.
+///
+/// On top of the raw `op +N` form this accepts `#` and `;` comments, blank lines, and labels
+/// (`name:`) either on their own line or in front of an instruction. `jmp` and `nop` can take a
+/// label as their operand, which is resolved to the relative offset of the labelled instruction.
+/// A label after the last instruction points at the end of the program. Numeric operands need
+/// their sign, as in the raw form.
+pub fn assemble(source: &str) -> Result<Executable, ParseError> {
+    let mut labels: HashMap<&str, usize> = HashMap::new();
+    let mut lines = Vec::new();
+
+    for (i, source_line) in source.lines().enumerate() {
+        let number = i + 1;
+        let line_text = source_line.trim();
+        let mut text = strip_comment(source_line).trim();
+
+        if let Some(colon) = text.find(':') {
+            let label = text[..colon].trim();
+            if !is_label(label) {
+                return Err(ParseError::InvalidLabel {
+                    line: number,
+                    text: line_text.to_string(),
+                });
+            }
+            if labels.insert(label, lines.len()).is_some() {
+                return Err(ParseError::DuplicateLabel {
+                    line: number,
+                    text: line_text.to_string(),
+                });
+            }
+            text = text[colon + 1..].trim();
+        }
+
+        if text.is_empty() {
+            continue;
+        }
+
+        let mut tokens = text.split_whitespace();
+        let (mnemonic, operand) = match (tokens.next(), tokens.next(), tokens.next()) {
+            (Some(mnemonic), Some(operand), None) => (mnemonic, operand),
+            _ => {
+                return Err(ParseError::MalformedOperand {
+                    line: number,
+                    text: line_text.to_string(),
+                })
+            }
+        };
+
+        let operand = if is_label(operand) {
+            Operand::Label(operand)
+        } else {
+            Operand::Value(parse_operand(number, line_text, operand)?)
+        };
+
+        lines.push(Line {
+            number,
+            text: line_text,
+            mnemonic,
+            operand,
+        });
+    }
+
+    let ops = lines
+        .iter()
+        .enumerate()
+        .map(|(addr, line)| {
+            let val = match line.operand {
+                Operand::Value(val) => val,
+                // acc has nothing to point at
+                Operand::Label(_) if line.mnemonic == "acc" => {
+                    return Err(ParseError::MalformedOperand {
+                        line: line.number,
+                        text: line.text.to_string(),
+                    })
+                }
+                Operand::Label(label) => {
+                    let target = labels
+                        .get(label)
+                        .ok_or_else(|| ParseError::UndefinedLabel {
+                            line: line.number,
+                            text: line.text.to_string(),
+                        })?;
+                    *target as i32 - addr as i32
+                }
+            };
+
+            match line.mnemonic {
+                "acc" => Ok(Op::Acc(val)),
+                "jmp" => Ok(Op::Jmp(val)),
+                "nop" => Ok(Op::Nop(val)),
+                _ => Err(ParseError::UnknownMnemonic {
+                    line: line.number,
+                    text: line.text.to_string(),
+                }),
+            }
+        })
+        .collect::<Result<_, _>>()?;
+
+    Ok(Executable(ops))
+}
+
+fn strip_comment(line: &str) -> &str {
+    match line.find(['#', ';']) {
+        Some(i) => &line[..i],
+        None => line,
+    }
+}
+
+fn is_label(s: &str) -> bool {
+    let mut chars = s.chars();
+
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {
+            chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        }
+        _ => false,
+    }
+}
+
+/// Printable listing of an executable. Without any options it prints the same canonical text as
+/// `Executable`'s `Display` implementation.
+pub struct Disassembly<'a> {
+    exe: &'a Executable,
+    addresses: bool,
+    targets: bool,
+}
+
+impl<'a> Disassembly<'a> {
+    pub fn new(exe: &'a Executable) -> Self {
+        Self {
+            exe,
+            addresses: false,
+            targets: false,
+        }
+    }
+
+    /// Prefix each instruction with its address
+    pub fn addresses(mut self, addresses: bool) -> Self {
+        self.addresses = addresses;
+        self
+    }
+
+    /// Annotate `jmp` and `nop` instructions with the absolute address they point at
+    pub fn targets(mut self, targets: bool) -> Self {
+        self.targets = targets;
+        self
+    }
+}
+
+impl fmt::Display for Disassembly<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let width = self.exe.0.len().to_string().len();
+
+        for (addr, op) in self.exe.0.iter().enumerate() {
+            if self.addresses {
+                write!(f, "{:>width$}  ", addr, width = width)?;
+            }
+
+            write!(f, "{}", op)?;
+
+            if self.targets {
+                match *op {
+                    Op::Jmp(i) | Op::Nop(i) => write!(f, "  ; -> {}", addr as i64 + i as i64)?,
+                    Op::Acc(_) => {}
+                }
+            }
+
+            writeln!(f)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{example, EXAMPLE};
+    use std::io::Cursor;
+
+    #[test]
+    fn resolves_labels() {
+        let source = "\
+            # the example program, written with labels
+            nop +0
+            loop: acc +1
+            jmp skip      ; forwards
+            back:
+            acc +3
+            jmp loop
+            acc -99
+            skip: acc +1
+            jmp back
+
+            acc +6";
+
+        assert_eq!(assemble(source).unwrap(), example());
+    }
+
+    #[test]
+    fn label_at_end_points_past_last_instruction() {
+        let exe = assemble("jmp end\nacc +1\nend:").unwrap();
+
+        assert_eq!(exe.0, vec![Op::Jmp(2), Op::Acc(1)]);
+    }
+
+    #[test]
+    fn rejects_bad_labels() {
+        assert!(matches!(
+            assemble("nop +0\njmp nowhere"),
+            Err(ParseError::UndefinedLabel { line: 2, .. })
+        ));
+        assert!(matches!(
+            assemble("a: nop +0\na: nop +0"),
+            Err(ParseError::DuplicateLabel { line: 2, .. })
+        ));
+        assert!(matches!(
+            assemble("a: acc a"),
+            Err(ParseError::MalformedOperand { line: 1, .. })
+        ));
+        assert!(matches!(
+            assemble("1a: nop +0"),
+            Err(ParseError::InvalidLabel { line: 1, .. })
+        ));
+        assert!(matches!(
+            assemble("mul +2"),
+            Err(ParseError::UnknownMnemonic { line: 1, .. })
+        ));
+        assert_eq!(
+            assemble("  jmp nowhere ; lost").unwrap_err().to_string(),
+            "line 1: undefined label in `jmp nowhere ; lost`"
+        );
+    }
+
+    #[test]
+    fn operands_need_one_sign() {
+        assert_eq!(assemble("acc -5").unwrap().0, vec![Op::Acc(-5)]);
+
+        for bad in ["acc 5", "acc --5", "acc +-5", "acc +", "acc +1 +2"].iter() {
+            assert!(
+                matches!(
+                    assemble(bad),
+                    Err(ParseError::MalformedOperand { line: 1, .. })
+                ),
+                "{}",
+                bad
+            );
+        }
+        assert!(matches!(
+            assemble("acc +99999999999"),
+            Err(ParseError::OperandOutOfRange { line: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn round_trips_through_text() {
+        let exe = Executable::from_reader(&mut Cursor::new(EXAMPLE)).unwrap();
+
+        assert_eq!(exe.to_string(), EXAMPLE);
+        assert_eq!(assemble(&exe.to_string()).unwrap(), exe);
+
+        // target annotations are comments, so they don't get in the way of reassembly
+        let annotated = exe.disassemble().targets(true).to_string();
+        assert_eq!(assemble(&annotated).unwrap(), exe);
+
+        let labelled = assemble("start: acc +1\nnop start\njmp start").unwrap();
+        let reparsed = Executable::from_reader(&mut Cursor::new(labelled.to_string())).unwrap();
+        assert_eq!(reparsed, labelled);
+    }
+
+    #[test]
+    fn prints_addresses_and_targets() {
+        let exe = example();
+
+        let listing = exe.disassemble().addresses(true).targets(true).to_string();
+        let lines: Vec<_> = listing.lines().collect();
+
+        assert_eq!(lines[0], "0  nop +0  ; -> 0");
+        assert_eq!(lines[1], "1  acc +1");
+        assert_eq!(lines[4], "4  jmp -3  ; -> 1");
+    }
+}
@@ -0,0 +1,26 @@
+use aoc08::{Executable, Result};
+use std::env;
+use std::io::{self, Write};
+
+const USAGE: &str = "Usage: compile [--decompile] < input > output";
+
+/// Converts programs to bytecode, or back to text with `--decompile`
+fn main() -> Result<()> {
+    let decompile = match env::args().nth(1).as_deref() {
+        None => false,
+        Some("--decompile") => true,
+        Some(_) => return Err(USAGE.into()),
+    };
+
+    let exe = Executable::load(&mut io::stdin().lock())?;
+
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    if decompile {
+        write!(out, "{}", exe)?;
+    } else {
+        exe.write_bytecode(&mut out)?;
+    }
+
+    Ok(())
+}
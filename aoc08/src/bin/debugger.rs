@@ -0,0 +1,318 @@
+use aoc08::{assemble, Executable, Op, Program, Result, Termination};
+use std::collections::HashSet;
+use std::env;
+use std::fs;
+use std::io::{self, BufRead, Write};
+
+const HELP: &str = "\
+commands:
+  s, step [n]          execute n instructions (default 1)
+  c, continue          run until a breakpoint, the end of the program, or a loop
+  b, break <addr>      break before the instruction at <addr> is executed
+  b, break acc <n>     break when the accumulator becomes <n>
+  d, delete <addr>     remove an address breakpoint
+  d, delete acc <n>    remove an accumulator breakpoint
+  breaks               list breakpoints
+  p, print             show pc, acc and the next instruction
+  l, list [addr] [n]   list n instructions around addr (default pc and 5)
+  patch <addr> <op>    replace the instruction at addr, e.g. `patch 7 nop -4`
+  r, reset             set pc and acc back to 0
+  h, help              show this message
+  q, quit              exit the debugger";
+
+/// Why `continue` handed control back to the user
+enum Stop {
+    Address(usize),
+    Acc(i32),
+    Loop(usize),
+    Halted(Termination),
+}
+
+struct Debugger<'a> {
+    program: Program<'a>,
+    addr_breaks: HashSet<usize>,
+    acc_breaks: HashSet<i32>,
+    /// Addresses executed since the last reset, used to stop `continue` when the program loops
+    executed: HashSet<usize>,
+}
+
+impl<'a> Debugger<'a> {
+    fn new(exe: &'a mut Executable) -> Self {
+        Self {
+            program: Program::new(exe),
+            addr_breaks: HashSet::new(),
+            acc_breaks: HashSet::new(),
+            executed: HashSet::new(),
+        }
+    }
+
+    fn step(&mut self) -> Option<Termination> {
+        let pc = self.program.pc();
+        let termination = self.program.step();
+
+        if termination.is_none() {
+            self.executed.insert(pc);
+        }
+
+        termination
+    }
+
+    fn resume(&mut self) -> Stop {
+        // always move past the instruction we're stopped on, otherwise we'd stop on the same
+        // breakpoint forever
+        let mut first = true;
+
+        loop {
+            let pc = self.program.pc();
+
+            if !first && self.addr_breaks.contains(&pc) {
+                return Stop::Address(pc);
+            }
+            if self.executed.contains(&pc) {
+                return Stop::Loop(pc);
+            }
+
+            let acc = self.program.acc;
+            if let Some(termination) = self.step() {
+                return Stop::Halted(termination);
+            }
+            first = false;
+
+            // only when acc changes, so a nop or jmp after an acc breakpoint doesn't stop again
+            if self.program.acc != acc && self.acc_breaks.contains(&self.program.acc) {
+                return Stop::Acc(self.program.acc);
+            }
+        }
+    }
+
+    /// Runs a single command, returns false when the user asks to quit
+    fn command<W: Write>(&mut self, line: &str, out: &mut W) -> Result<bool> {
+        let args: Vec<&str> = line.split_whitespace().collect();
+
+        match args.as_slice() {
+            [] => {}
+            ["q"] | ["quit"] => return Ok(false),
+            ["h"] | ["help"] => writeln!(out, "{}", HELP)?,
+            ["s"] | ["step"] => self.step_n(1, out)?,
+            ["s", n] | ["step", n] => self.step_n(n.parse()?, out)?,
+            ["c"] | ["continue"] => {
+                match self.resume() {
+                    Stop::Address(pc) => writeln!(out, "breakpoint at {}", pc)?,
+                    Stop::Acc(acc) => writeln!(out, "accumulator reached {}", acc)?,
+                    Stop::Loop(pc) => writeln!(out, "loop: {} has already been executed", pc)?,
+                    Stop::Halted(termination) => halted(termination, out)?,
+                }
+                self.print(out)?;
+            }
+            ["b", "acc", n] | ["break", "acc", n] => {
+                self.acc_breaks.insert(n.parse()?);
+            }
+            ["b", addr] | ["break", addr] => {
+                self.addr_breaks.insert(addr.parse()?);
+            }
+            ["d", "acc", n] | ["delete", "acc", n] => {
+                if !self.acc_breaks.remove(&n.parse()?) {
+                    writeln!(out, "no breakpoint on acc {}", n)?;
+                }
+            }
+            ["d", addr] | ["delete", addr] => {
+                if !self.addr_breaks.remove(&addr.parse()?) {
+                    writeln!(out, "no breakpoint at {}", addr)?;
+                }
+            }
+            ["breaks"] => {
+                let mut addrs: Vec<_> = self.addr_breaks.iter().collect();
+                addrs.sort();
+                let mut accs: Vec<_> = self.acc_breaks.iter().collect();
+                accs.sort();
+
+                for addr in addrs {
+                    writeln!(out, "address {}", addr)?;
+                }
+                for acc in accs {
+                    writeln!(out, "acc {}", acc)?;
+                }
+            }
+            ["p"] | ["print"] => self.print(out)?,
+            ["l"] | ["list"] => self.list(self.program.pc(), 5, out)?,
+            ["l", addr] | ["list", addr] => self.list(addr.parse()?, 5, out)?,
+            ["l", addr, n] | ["list", addr, n] => self.list(addr.parse()?, n.parse()?, out)?,
+            ["patch", addr, op, val] => {
+                let addr = addr.parse()?;
+                let op: Op = format!("{} {}", op, val).parse()?;
+                let old = self.program.patch(addr, op.clone())?;
+                // the old history no longer says anything about whether the program loops
+                self.executed.clear();
+                writeln!(out, "{}: {} -> {}", addr, old, op)?;
+            }
+            ["r"] | ["reset"] => {
+                self.program.reset();
+                self.executed.clear();
+                self.print(out)?;
+            }
+            _ => writeln!(out, "unrecognized command, try `help`")?,
+        }
+
+        Ok(true)
+    }
+
+    fn step_n<W: Write>(&mut self, n: usize, out: &mut W) -> Result<()> {
+        for _ in 0..n {
+            if let Some(termination) = self.step() {
+                halted(termination, out)?;
+                break;
+            }
+        }
+
+        self.print(out)
+    }
+
+    fn print<W: Write>(&self, out: &mut W) -> Result<()> {
+        write!(out, "pc: {} acc: {}", self.program.pc(), self.program.acc)?;
+
+        match self.program.current() {
+            Some(op) => writeln!(out, " next: {}", op)?,
+            None => writeln!(out, " (end of program)")?,
+        }
+
+        Ok(())
+    }
+
+    fn list<W: Write>(&self, addr: usize, n: usize, out: &mut W) -> Result<()> {
+        let exe = self.program.executable();
+        let start = addr.saturating_sub(n / 2);
+        let end = start.saturating_add(n).min(exe.len());
+
+        for i in start..end {
+            let marker = if i == self.program.pc() { ">" } else { " " };
+            let brk = if self.addr_breaks.contains(&i) {
+                "*"
+            } else {
+                " "
+            };
+            writeln!(out, "{}{} {:>4}  {}", marker, brk, i, exe.get(i).unwrap())?;
+        }
+
+        Ok(())
+    }
+}
+
+fn halted<W: Write>(termination: Termination, out: &mut W) -> Result<()> {
+    match termination {
+        Termination::Eof => writeln!(out, "program terminated")?,
+        termination => writeln!(out, "program stopped: {}", termination)?,
+    }
+
+    Ok(())
+}
+
+fn main() -> Result<()> {
+    let path = env::args().nth(1).ok_or("Usage: debugger <program file>")?;
+    let mut exe = assemble(&fs::read_to_string(path)?)?;
+    let mut debugger = Debugger::new(&mut exe);
+
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+
+    write!(out, "(dbg) ")?;
+    out.flush()?;
+
+    for line in stdin.lock().lines() {
+        match debugger.command(&line?, &mut out) {
+            Ok(true) => {}
+            Ok(false) => break,
+            Err(e) => writeln!(out, "error: {}", e)?,
+        }
+
+        write!(out, "(dbg) ")?;
+        out.flush()?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn run(exe: &mut Executable, commands: &[&str]) -> String {
+        let mut debugger = Debugger::new(exe);
+        let mut out = Vec::new();
+
+        for command in commands {
+            debugger.command(command, &mut out).unwrap();
+        }
+
+        String::from_utf8(out).unwrap()
+    }
+
+    /// Same program as the library's test fixture, which binaries can't see
+    fn example() -> Executable {
+        assemble(include_str!("../../input/example.txt")).unwrap()
+    }
+
+    #[test]
+    fn stops_on_loop() {
+        let out = run(&mut example(), &["continue"]);
+
+        assert_eq!(
+            out,
+            "loop: 1 has already been executed\npc: 1 acc: 5 next: acc +1\n"
+        );
+    }
+
+    #[test]
+    fn stops_on_breakpoints() {
+        let out = run(&mut example(), &["break 6", "c", "break acc 5", "c"]);
+
+        assert_eq!(
+            out,
+            "breakpoint at 6\npc: 6 acc: 1 next: acc +1\n\
+             accumulator reached 5\npc: 4 acc: 5 next: jmp -3\n"
+        );
+    }
+
+    #[test]
+    fn acc_breakpoint_needs_a_change() {
+        let mut exe = assemble("acc +5\nnop +0\njmp +1\nacc +0\nacc +1").unwrap();
+        let out = run(&mut exe, &["break acc 5", "c", "c"]);
+
+        assert_eq!(
+            out,
+            "accumulator reached 5\npc: 1 acc: 5 next: nop +0\n\
+             program terminated\npc: 5 acc: 6 (end of program)\n"
+        );
+    }
+
+    #[test]
+    fn patch_fixes_loop() {
+        let out = run(&mut example(), &["step 3", "patch 7 nop -4", "c"]);
+
+        assert_eq!(
+            out,
+            "pc: 6 acc: 1 next: acc +1\n7: jmp -4 -> nop -4\nprogram terminated\n\
+             pc: 9 acc: 8 (end of program)\n"
+        );
+    }
+
+    #[test]
+    fn reports_wild_jump() {
+        let out = run(&mut assemble("acc +1\njmp -5").unwrap(), &["c"]);
+
+        assert_eq!(
+            out,
+            "program stopped: jump below zero at 1\npc: 1 acc: 1 next: jmp -5\n"
+        );
+    }
+
+    #[test]
+    fn lists_around_address() {
+        let out = run(&mut example(), &["list 7 4", "list 2 18446744073709551615"]);
+        let lines: Vec<_> = out.lines().collect();
+
+        assert_eq!(lines[0], "      5  acc -99");
+        assert_eq!(lines[3], "      8  acc +6");
+        assert_eq!(lines.len(), 4 + 9);
+    }
+}
@@ -0,0 +1,35 @@
+use aoc08::{assemble, Profile, Result};
+use std::env;
+use std::io::{self, Read};
+
+const USAGE: &str = "Usage: profiler [--json] [--steps <n>] < program";
+
+fn main() -> Result<()> {
+    let mut json = false;
+    let mut step_limit = None;
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--json" => json = true,
+            "--steps" => step_limit = Some(args.next().ok_or(USAGE)?.parse()?),
+            _ => return Err(USAGE.into()),
+        }
+    }
+
+    let mut source = String::new();
+    io::stdin().read_to_string(&mut source)?;
+    let exe = assemble(&source)?;
+
+    let profile = Profile::collect(&exe, step_limit);
+
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    if json {
+        profile.write_json(&mut out)?;
+    } else {
+        profile.write_listing(&mut out)?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,321 @@
+//! Binary format for executables.
+//!
+//! ```text
+//! magic     b"AOC8"
+//! version   1 byte
+//! length    varint, number of instructions
+//! ops       opcode byte (0 = acc, 1 = jmp, 2 = nop) followed by a zigzag varint operand
+//! checksum  CRC-32 of everything before it, 4 bytes little endian
+//! ```
+use crate::{Executable, Op};
+use std::io::{self, Read, Write};
+use thiserror::Error;
+
+pub const MAGIC: &[u8; 4] = b"AOC8";
+pub const VERSION: u8 = 1;
+
+#[derive(Error, Debug)]
+pub enum BytecodeError {
+    #[error("Error reading bytecode")]
+    Io(#[from] io::Error),
+    #[error("Not an aoc08 bytecode file")]
+    BadMagic,
+    #[error("Unsupported bytecode version {0}")]
+    UnsupportedVersion(u8),
+    #[error("Bytecode ends unexpectedly")]
+    Truncated,
+    #[error("Unknown opcode {opcode} at offset {offset}")]
+    BadOpcode { offset: usize, opcode: u8 },
+    #[error("Varint at offset {offset} is too large")]
+    VarintOverflow { offset: usize },
+    #[error("Checksum mismatch, expected {expected:08x} but found {found:08x}")]
+    ChecksumMismatch { expected: u32, found: u32 },
+    #[error("Unexpected data after checksum")]
+    TrailingData,
+}
+
+impl Executable {
+    pub fn to_bytecode(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(MAGIC.len() + 1 + self.0.len() * 3 + 8);
+
+        bytes.extend_from_slice(MAGIC);
+        bytes.push(VERSION);
+        write_varint(&mut bytes, self.0.len() as u64);
+
+        for op in self.0.iter() {
+            let (opcode, val) = match *op {
+                Op::Acc(i) => (0, i),
+                Op::Jmp(i) => (1, i),
+                Op::Nop(i) => (2, i),
+            };
+            bytes.push(opcode);
+            write_varint(&mut bytes, zigzag(val));
+        }
+
+        let checksum = crc32(&bytes);
+        bytes.extend_from_slice(&checksum.to_le_bytes());
+
+        bytes
+    }
+
+    pub fn from_bytecode(bytes: &[u8]) -> Result<Self, BytecodeError> {
+        if bytes.len() < MAGIC.len() || &bytes[..MAGIC.len()] != MAGIC {
+            return Err(BytecodeError::BadMagic);
+        }
+        if bytes.len() < MAGIC.len() + 1 + 4 {
+            return Err(BytecodeError::Truncated);
+        }
+
+        let (body, checksum) = bytes.split_at(bytes.len() - 4);
+        let mut reader = Reader {
+            bytes: body,
+            offset: MAGIC.len(),
+        };
+
+        let version = reader.byte()?;
+        if version != VERSION {
+            return Err(BytecodeError::UnsupportedVersion(version));
+        }
+
+        let expected = u32::from_le_bytes([checksum[0], checksum[1], checksum[2], checksum[3]]);
+        let found = crc32(body);
+        if expected != found {
+            return Err(BytecodeError::ChecksumMismatch { expected, found });
+        }
+
+        let len = reader.varint()?;
+        // every instruction takes at least two bytes, don't trust the length for the allocation
+        let mut ops = Vec::with_capacity((len as usize).min(body.len() / 2));
+
+        for _ in 0..len {
+            let offset = reader.offset;
+            let opcode = reader.byte()?;
+            let val = unzigzag(reader.varint()?)
+                .ok_or(BytecodeError::VarintOverflow { offset: offset + 1 })?;
+
+            ops.push(match opcode {
+                0 => Op::Acc(val),
+                1 => Op::Jmp(val),
+                2 => Op::Nop(val),
+                opcode => return Err(BytecodeError::BadOpcode { offset, opcode }),
+            });
+        }
+
+        if reader.offset != body.len() {
+            return Err(BytecodeError::TrailingData);
+        }
+
+        Ok(Self(ops))
+    }
+
+    pub fn write_bytecode<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(&self.to_bytecode())
+    }
+
+    pub fn read_bytecode<R: Read>(reader: &mut R) -> Result<Self, BytecodeError> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+
+        Self::from_bytecode(&bytes)
+    }
+
+    /// Loads either bytecode or text, depending on whether the input starts with the bytecode
+    /// magic number. Text is read with the assembler, so labels and comments are allowed, and
+    /// its errors are `ParseError`s.
+    pub fn load<R: Read>(reader: &mut R) -> crate::Result<Self> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+
+        if is_bytecode(&bytes) {
+            return Ok(Self::from_bytecode(&bytes)?);
+        }
+
+        Ok(crate::assemble(&String::from_utf8(bytes)?)?)
+    }
+}
+
+/// Whether the data starts with the bytecode magic number
+pub fn is_bytecode(bytes: &[u8]) -> bool {
+    bytes.starts_with(MAGIC)
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl Reader<'_> {
+    fn byte(&mut self) -> Result<u8, BytecodeError> {
+        let byte = *self
+            .bytes
+            .get(self.offset)
+            .ok_or(BytecodeError::Truncated)?;
+        self.offset += 1;
+
+        Ok(byte)
+    }
+
+    fn varint(&mut self) -> Result<u64, BytecodeError> {
+        let offset = self.offset;
+        let mut value = 0u64;
+
+        for shift in (0..64).step_by(7) {
+            let byte = self.byte()?;
+            value |= ((byte & 0x7f) as u64) << shift;
+
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+
+        Err(BytecodeError::VarintOverflow { offset })
+    }
+}
+
+fn write_varint(bytes: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        bytes.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8);
+}
+
+/// Maps signed integers onto unsigned ones so that small magnitudes stay small: 0, -1, 1, -2...
+fn zigzag(value: i32) -> u64 {
+    ((value << 1) ^ (value >> 31)) as u32 as u64
+}
+
+fn unzigzag(value: u64) -> Option<i32> {
+    if value > u32::MAX as u64 {
+        return None;
+    }
+    let value = value as u32;
+
+    Some((value >> 1) as i32 ^ -((value & 1) as i32))
+}
+
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xedb8_8320 & mask);
+        }
+    }
+
+    !crc
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{assemble, ParseError, EXAMPLE};
+    use std::io::Cursor;
+
+    /// The shared example with the largest and smallest operands on the end
+    fn example() -> Executable {
+        assemble(&format!(
+            "{}acc +2147483647
+acc -2147483648
+",
+            EXAMPLE
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn round_trips() {
+        let exe = example();
+        let bytes = exe.to_bytecode();
+
+        assert_eq!(&bytes[..5], b"AOC8\x01");
+        assert_eq!(&bytes[5..9], &[11, 2, 0, 0]);
+        assert_eq!(Executable::from_bytecode(&bytes).unwrap(), exe);
+        assert_eq!(
+            Executable::from_bytecode(&assemble("").unwrap().to_bytecode())
+                .unwrap()
+                .0,
+            vec![]
+        );
+    }
+
+    #[test]
+    fn zigzag_round_trips() {
+        for &i in [0, -1, 1, -64, 64, i32::MAX, i32::MIN].iter() {
+            assert_eq!(unzigzag(zigzag(i)), Some(i));
+        }
+        assert_eq!(zigzag(-1), 1);
+        assert_eq!(unzigzag(u32::MAX as u64 + 1), None);
+    }
+
+    #[test]
+    fn detects_corruption() {
+        let mut bytes = example().to_bytecode();
+        bytes[7] ^= 0x01;
+
+        assert!(matches!(
+            Executable::from_bytecode(&bytes),
+            Err(BytecodeError::ChecksumMismatch { .. })
+        ));
+
+        let bytes = example().to_bytecode();
+        assert!(matches!(
+            Executable::from_bytecode(&bytes[..bytes.len() - 1]),
+            Err(BytecodeError::ChecksumMismatch { .. })
+        ));
+        assert!(matches!(
+            Executable::from_bytecode(b"nop +0\n"),
+            Err(BytecodeError::BadMagic)
+        ));
+
+        let mut bytes = example().to_bytecode();
+        bytes[4] = 2;
+        assert!(matches!(
+            Executable::from_bytecode(&bytes),
+            Err(BytecodeError::UnsupportedVersion(2))
+        ));
+    }
+
+    #[test]
+    fn rejects_bad_opcode() {
+        let mut bytes = b"AOC8\x01\x01\x07\x00".to_vec();
+        let checksum = crc32(&bytes);
+        bytes.extend_from_slice(&checksum.to_le_bytes());
+
+        assert!(matches!(
+            Executable::from_bytecode(&bytes),
+            Err(BytecodeError::BadOpcode {
+                offset: 6,
+                opcode: 7
+            })
+        ));
+    }
+
+    #[test]
+    fn loads_text_or_bytecode() {
+        let exe = example();
+
+        let mut text = Cursor::new(exe.to_string());
+        assert_eq!(Executable::load(&mut text).unwrap(), exe);
+
+        let mut bytes = Cursor::new(exe.to_bytecode());
+        assert_eq!(Executable::load(&mut bytes).unwrap(), exe);
+
+        // a pipe can hand over fewer bytes than the magic number at a time
+        let mut trickle = io::BufReader::with_capacity(1, Cursor::new(exe.to_bytecode()));
+        assert_eq!(Executable::load(&mut trickle).unwrap(), exe);
+
+        let err = Executable::load(&mut Cursor::new("nop +0\nmul +2\n")).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<ParseError>(),
+            Some(ParseError::UnknownMnemonic { line: 2, .. })
+        ));
+    }
+
+    #[test]
+    fn checksum_matches_reference() {
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+    }
+}
@@ -0,0 +1,277 @@
+use crate::{Executable, Op};
+use std::collections::VecDeque;
+use std::io::{self, Write};
+
+/// Outcome of looking for a single `jmp`/`nop` swap that makes a program terminate
+#[derive(PartialEq, Debug)]
+pub enum Repair {
+    /// The program already runs off the end without any changes
+    Unneeded,
+    /// Swapping the instruction at this address makes the program terminate
+    Swap(usize),
+    /// No single swap makes the program terminate
+    Unfixable,
+    /// More than one swap works, every candidate address is listed in ascending order
+    Ambiguous(Vec<usize>),
+}
+
+/// Control flow graph of an executable. Every instruction has exactly one successor, and the
+/// address one past the last instruction is the exit node. Jumps to any other address outside of
+/// the program lead nowhere, so they never count as terminating.
+pub struct Cfg<'a> {
+    exe: &'a Executable,
+    /// `terminates[i]` is true if execution starting at `i` reaches the exit node, the exit node
+    /// itself is included at the end
+    terminates: Vec<bool>,
+}
+
+impl<'a> Cfg<'a> {
+    pub fn new(exe: &'a Executable) -> Self {
+        let len = exe.0.len();
+
+        // reverse edges, including the exit node at index len
+        let mut predecessors = vec![Vec::new(); len + 1];
+        for (i, op) in exe.0.iter().enumerate() {
+            if let Some(succ) = target(i, op, len) {
+                predecessors[succ].push(i);
+            }
+        }
+
+        let mut terminates = vec![false; len + 1];
+        terminates[len] = true;
+        let mut queue: VecDeque<usize> = VecDeque::new();
+        queue.push_back(len);
+
+        while let Some(node) = queue.pop_front() {
+            for &pred in predecessors[node].iter() {
+                if !terminates[pred] {
+                    terminates[pred] = true;
+                    queue.push_back(pred);
+                }
+            }
+        }
+
+        Self { exe, terminates }
+    }
+
+    /// The address executed after `addr`, `None` if the instruction jumps outside of the program
+    pub fn successor(&self, addr: usize) -> Option<usize> {
+        target(addr, &self.exe.0[addr], self.exe.0.len())
+    }
+
+    /// Whether execution starting at `addr` eventually runs off the end of the program
+    pub fn terminates(&self, addr: usize) -> bool {
+        self.terminates.get(addr).copied().unwrap_or(false)
+    }
+
+    /// Addresses executed starting at `start`, up to the point where the program terminates,
+    /// jumps out of bounds, or revisits an instruction
+    pub fn path(&self, start: usize) -> Vec<usize> {
+        let len = self.exe.0.len();
+        let mut visited = vec![false; len];
+        let mut path = Vec::new();
+        let mut pc = Some(start);
+
+        while let Some(addr) = pc {
+            if addr >= len || visited[addr] {
+                break;
+            }
+            visited[addr] = true;
+            path.push(addr);
+            pc = self.successor(addr);
+        }
+
+        path
+    }
+
+    /// Every `jmp`/`nop` that can be swapped so that execution from `start` terminates.
+    ///
+    /// Only instructions on the path from `start` matter, and swapping one of them works exactly
+    /// when its alternative successor terminates in the unmodified graph: if that route passed
+    /// back through the swapped instruction then the original program would already terminate.
+    pub fn repairs(&self, start: usize) -> Vec<usize> {
+        let len = self.exe.0.len();
+        let mut repairs: Vec<usize> = self
+            .path(start)
+            .into_iter()
+            .filter(|&addr| {
+                let swapped = match self.exe.0[addr].swapped() {
+                    Some(swapped) => swapped,
+                    None => return false,
+                };
+
+                target(addr, &swapped, len).is_some_and(|succ| self.terminates[succ])
+            })
+            .collect();
+
+        repairs.sort_unstable();
+        repairs
+    }
+
+    /// Finds the single swap that makes execution from `start` terminate
+    pub fn repair(&self, start: usize) -> Repair {
+        if self.terminates(start) {
+            return Repair::Unneeded;
+        }
+
+        let mut repairs = self.repairs(start);
+        match repairs.len() {
+            0 => Repair::Unfixable,
+            1 => Repair::Swap(repairs.pop().unwrap()),
+            _ => Repair::Ambiguous(repairs),
+        }
+    }
+
+    /// Writes the graph in Graphviz DOT format. Edges that would be taken if the instruction was
+    /// swapped are drawn dashed, and instructions that terminate are filled in.
+    pub fn write_dot<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let len = self.exe.0.len();
+
+        writeln!(writer, "digraph program {{")?;
+        writeln!(writer, "    node [shape=box, fontname=monospace];")?;
+        writeln!(writer, "    exit [label=\"exit\", shape=doublecircle];")?;
+
+        for (addr, op) in self.exe.0.iter().enumerate() {
+            let style = if self.terminates[addr] {
+                ", style=filled"
+            } else {
+                ""
+            };
+            writeln!(
+                writer,
+                "    n{} [label=\"{}: {}\"{}];",
+                addr, addr, op, style
+            )?;
+        }
+
+        let mut out_of_bounds = false;
+        for (addr, op) in self.exe.0.iter().enumerate() {
+            write_edge(
+                writer,
+                addr,
+                target(addr, op, len),
+                len,
+                "",
+                &mut out_of_bounds,
+            )?;
+
+            let swapped = match op.swapped() {
+                Some(swapped) => swapped,
+                None => continue,
+            };
+            let succ = target(addr, &swapped, len);
+            write_edge(
+                writer,
+                addr,
+                succ,
+                len,
+                " [style=dashed]",
+                &mut out_of_bounds,
+            )?;
+        }
+
+        if out_of_bounds {
+            writeln!(writer, "    oob [label=\"out of bounds\", shape=octagon];")?;
+        }
+
+        writeln!(writer, "}}")
+    }
+}
+
+fn write_edge<W: Write>(
+    writer: &mut W,
+    from: usize,
+    to: Option<usize>,
+    len: usize,
+    attrs: &str,
+    out_of_bounds: &mut bool,
+) -> io::Result<()> {
+    match to {
+        Some(to) if to == len => writeln!(writer, "    n{} -> exit{};", from, attrs),
+        Some(to) => writeln!(writer, "    n{} -> n{}{};", from, to, attrs),
+        None => {
+            *out_of_bounds = true;
+            writeln!(writer, "    n{} -> oob{};", from, attrs)
+        }
+    }
+}
+
+/// Successor of the instruction at `addr`, if it lies within the program or is the exit node
+fn target(addr: usize, op: &Op, len: usize) -> Option<usize> {
+    let next = match *op {
+        Op::Acc(_) | Op::Nop(_) => addr as i64 + 1,
+        Op::Jmp(i) => addr as i64 + i as i64,
+    };
+
+    if next >= 0 && next <= len as i64 {
+        Some(next as usize)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{assemble, example};
+
+    #[test]
+    fn finds_single_repair() {
+        let exe = example();
+        let cfg = Cfg::new(&exe);
+
+        assert_eq!(cfg.path(0), vec![0, 1, 2, 6, 7, 3, 4]);
+        assert!(cfg.terminates(8));
+        assert!(!cfg.terminates(0));
+        assert_eq!(cfg.repair(0), Repair::Swap(7));
+    }
+
+    #[test]
+    fn reports_unneeded_unfixable_and_ambiguous() {
+        let exe = assemble("acc +1\nnop +0").unwrap();
+        assert_eq!(Cfg::new(&exe).repair(0), Repair::Unneeded);
+
+        let exe = assemble("acc +1\njmp -1\njmp -2").unwrap();
+        assert_eq!(Cfg::new(&exe).repair(0), Repair::Unfixable);
+
+        let exe = assemble("nop +3\njmp -1\nacc +1").unwrap();
+        assert_eq!(Cfg::new(&exe).repair(0), Repair::Ambiguous(vec![0, 1]));
+    }
+
+    #[test]
+    fn wild_jumps_do_not_terminate() {
+        let exe = assemble("nop +0\njmp -5\njmp +4").unwrap();
+        let cfg = Cfg::new(&exe);
+
+        assert_eq!(cfg.successor(1), None);
+        assert_eq!(cfg.successor(2), None);
+        assert_eq!(cfg.path(0), vec![0, 1]);
+        assert_eq!(cfg.repair(0), Repair::Unfixable);
+
+        let exe = assemble("nop +0\njmp -5\nacc +1").unwrap();
+        assert_eq!(Cfg::new(&exe).repair(0), Repair::Swap(1));
+    }
+
+    #[test]
+    fn writes_dot() {
+        let exe = assemble("nop +2\njmp -1").unwrap();
+        let mut out = Vec::new();
+        Cfg::new(&exe).write_dot(&mut out).unwrap();
+
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "\
+digraph program {
+    node [shape=box, fontname=monospace];
+    exit [label=\"exit\", shape=doublecircle];
+    n0 [label=\"0: nop +2\"];
+    n1 [label=\"1: jmp -1\"];
+    n0 -> n1;
+    n0 -> exit [style=dashed];
+    n1 -> n0;
+    n1 -> exit [style=dashed];
+}
+"
+        );
+    }
+}
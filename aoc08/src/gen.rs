@@ -0,0 +1,350 @@
+use crate::{Executable, Op};
+use fastrand::Rng;
+use std::convert::TryFrom;
+
+/// What running a generated program from address 0 is guaranteed to do
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum Shape {
+    /// Runs off the end without repeating an instruction
+    Terminating,
+    /// Repeats an instruction before it can run off the end
+    Looping,
+}
+
+/// Settings for generated programs
+#[derive(Clone, Debug)]
+pub struct Config {
+    /// Number of instructions
+    pub len: usize,
+    pub shape: Shape,
+    /// Largest jump distance in either direction, jumps on the execution path may go further when
+    /// there's nowhere left to go within range
+    pub max_jump: usize,
+    /// Chance of leaving an instruction with a jump rather than falling through to the next
+    pub jump_probability: f64,
+    /// `acc` operands are drawn from `-max_acc..=max_acc`, a negative value counts the same as
+    /// its magnitude
+    pub max_acc: i32,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            len: 100,
+            shape: Shape::Terminating,
+            max_jump: 20,
+            jump_probability: 0.3,
+            max_acc: 50,
+        }
+    }
+}
+
+/// Random executables for testing, seeded so that a program that finds a bug can be rebuilt
+pub struct Generator {
+    rng: Rng,
+}
+
+impl Generator {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            rng: Rng::with_seed(seed),
+        }
+    }
+
+    /// Builds a program by first choosing the path that execution will follow, which fixes the
+    /// instructions along it, and then filling every other address with random in-bounds
+    /// instructions. The path never revisits an address, and ends by either falling off the end
+    /// or jumping back to an earlier address on the path, depending on the shape.
+    pub fn generate(&mut self, config: &Config) -> Executable {
+        let len = config.len;
+        let mut ops: Vec<Option<Op>> = vec![None; len];
+        let mut visited = vec![false; len];
+        let mut path = Vec::new();
+
+        // an empty program can only terminate, whatever the shape
+        if len > 0 {
+            let mut pc = 0;
+            loop {
+                visited[pc] = true;
+                path.push(pc);
+
+                let next = self.choose_next(config, pc, &visited, path.len());
+                let (next, done) = match next {
+                    Some(next) => (next, false),
+                    None if config.shape == Shape::Terminating => (len, true),
+                    None => (path[self.rng.usize(..path.len())], true),
+                };
+
+                let offset = next as i32 - pc as i32;
+                ops[pc] = Some(if offset == 1 {
+                    self.fallthrough(config, pc)
+                } else {
+                    Op::Jmp(offset)
+                });
+
+                if done {
+                    break;
+                }
+                pc = next;
+            }
+        }
+
+        let ops = ops
+            .into_iter()
+            .enumerate()
+            .map(|(addr, op)| op.unwrap_or_else(|| self.filler(config, addr)))
+            .collect();
+
+        Executable(ops)
+    }
+
+    /// Picks the next unvisited address on the path, `None` once the path should end
+    fn choose_next(
+        &mut self,
+        config: &Config,
+        pc: usize,
+        visited: &[bool],
+        path_len: usize,
+    ) -> Option<usize> {
+        let len = visited.len();
+
+        // end the path early every now and then so that plenty of code is left off it
+        if self.rng.usize(..len.max(1) * 2) < path_len.min(len) / 4 {
+            return None;
+        }
+
+        if self.rng.f64() >= config.jump_probability && pc + 1 < len && !visited[pc + 1] {
+            return Some(pc + 1);
+        }
+
+        let lo = pc.saturating_sub(config.max_jump);
+        let hi = pc.saturating_add(config.max_jump).min(len - 1);
+        let nearby: Vec<usize> = (lo..=hi).filter(|&addr| !visited[addr]).collect();
+        if !nearby.is_empty() {
+            return Some(nearby[self.rng.usize(..nearby.len())]);
+        }
+
+        let anywhere: Vec<usize> = (0..len).filter(|&addr| !visited[addr]).collect();
+        if anywhere.is_empty() {
+            return None;
+        }
+
+        Some(anywhere[self.rng.usize(..anywhere.len())])
+    }
+
+    /// An instruction that moves on to the next address
+    fn fallthrough(&mut self, config: &Config, addr: usize) -> Op {
+        match self.rng.u8(..3) {
+            0 => Op::Nop(self.offset(config, addr)),
+            _ => Op::Acc(self.acc(config)),
+        }
+    }
+
+    /// A random instruction for an address that isn't on the execution path
+    fn filler(&mut self, config: &Config, addr: usize) -> Op {
+        match self.rng.u8(..3) {
+            0 => Op::Acc(self.acc(config)),
+            1 => Op::Jmp(self.offset(config, addr)),
+            _ => Op::Nop(self.offset(config, addr)),
+        }
+    }
+
+    /// A jump distance from `addr`, clamped so that it stays within the program. This keeps every
+    /// `nop` off the path valid as a `jmp`, and the other way round.
+    fn offset(&mut self, config: &Config, addr: usize) -> i32 {
+        let max_jump = i64::try_from(config.max_jump).unwrap_or(i64::MAX);
+        let offset = self.rng.i64(-max_jump..=max_jump);
+
+        ((addr as i64)
+            .saturating_add(offset)
+            .clamp(0, config.len as i64)
+            - addr as i64) as i32
+    }
+
+    fn acc(&mut self, config: &Config) -> i32 {
+        let max_acc = config.max_acc.checked_abs().unwrap_or(i32::MAX);
+
+        self.rng.i32(-max_acc..=max_acc)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{assemble, optimize, Cfg, Machine, Program, Repair, Termination};
+    use std::io::Cursor;
+    use std::sync::Arc;
+
+    /// Programs of every length up to 60 in both shapes, all from one generator. The looping ones
+    /// jump often and not far, so that their paths tangle.
+    fn sample() -> Vec<(Shape, Executable)> {
+        let mut generator = Generator::new(2020);
+        let mut programs = Vec::new();
+
+        for round in 0..5 {
+            for len in 1..=60 {
+                let terminating = Config {
+                    len,
+                    ..Config::default()
+                };
+                let looping = Config {
+                    len,
+                    shape: Shape::Looping,
+                    max_jump: 1 + (len + round) % 8,
+                    jump_probability: 0.6,
+                    ..Config::default()
+                };
+
+                programs.push((Shape::Terminating, generator.generate(&terminating)));
+                programs.push((Shape::Looping, generator.generate(&looping)));
+            }
+        }
+
+        programs
+    }
+
+    fn run(exe: &Executable) -> (Termination, i32) {
+        let mut exe = exe.clone();
+        let mut program = Program::new(&mut exe);
+        let termination = program.execute_from(0);
+
+        (termination, program.acc)
+    }
+
+    /// Tries every swap the slow way
+    fn brute_force_repairs(exe: &Executable) -> Vec<(usize, i32)> {
+        (0..exe.len())
+            .filter_map(|addr| {
+                let mut variant = exe.clone();
+                variant.patch(addr, exe.get(addr)?.swapped()?).unwrap();
+
+                match run(&variant) {
+                    (Termination::Eof, acc) => Some((addr, acc)),
+                    _ => None,
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn seed_fixes_the_programs() {
+        let config = Config::default();
+        let mut generator = Generator::new(7);
+        let program = generator.generate(&config);
+
+        assert_eq!(Generator::new(7).generate(&config), program);
+        assert_eq!(program.len(), 100);
+        // the generator moves on, so the next program is a new one
+        assert_ne!(generator.generate(&config), program);
+    }
+
+    #[test]
+    fn extreme_configs_generate() {
+        for &(max_jump, max_acc) in
+            [(usize::MAX, i32::MIN), (0, -5), (usize::MAX / 2, i32::MAX)].iter()
+        {
+            let config = Config {
+                max_jump,
+                max_acc,
+                ..Config::default()
+            };
+            let exe = Generator::new(3).generate(&config);
+
+            // operands this large can overflow the accumulator, but it still mustn't loop
+            assert!(!matches!(run(&exe).0, Termination::Loop { .. }), "{}", exe);
+            for (addr, op) in exe.0.iter().enumerate() {
+                match *op {
+                    Op::Acc(i) => assert!(i.unsigned_abs() <= max_acc.unsigned_abs()),
+                    Op::Jmp(i) | Op::Nop(i) => {
+                        assert!((0..=100).contains(&(addr as i64 + i as i64)), "{}", exe)
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn shape_is_guaranteed() {
+        for (shape, exe) in sample() {
+            let termination = run(&exe).0;
+            let expected = match shape {
+                Shape::Terminating => termination == Termination::Eof,
+                Shape::Looping => matches!(termination, Termination::Loop { .. }),
+            };
+
+            assert!(
+                expected,
+                "{:?} got {}
+{}",
+                shape, termination, exe
+            );
+        }
+    }
+
+    #[test]
+    fn round_trips_through_text_and_bytecode() {
+        for (_, exe) in sample() {
+            let text = exe.to_string();
+
+            assert_eq!(
+                Executable::from_reader(&mut Cursor::new(&text)).unwrap(),
+                exe
+            );
+            assert_eq!(assemble(&text).unwrap(), exe);
+            assert_eq!(Executable::from_bytecode(&exe.to_bytecode()).unwrap(), exe);
+        }
+    }
+
+    #[test]
+    fn repair_matches_brute_force() {
+        let looping = sample()
+            .into_iter()
+            .filter(|(shape, _)| *shape == Shape::Looping);
+
+        for (_, exe) in looping {
+            let oracle = brute_force_repairs(&exe);
+            let context = exe.to_string();
+
+            let expected = match oracle.len() {
+                0 => Repair::Unfixable,
+                1 => Repair::Swap(oracle[0].0),
+                _ => Repair::Ambiguous(oracle.iter().map(|(addr, _)| *addr).collect()),
+            };
+            assert_eq!(Cfg::new(&exe).repair(0), expected, "{}", context);
+
+            let mut repaired = exe.clone();
+            let mut program = Program::new(&mut repaired);
+            match oracle.first() {
+                Some(&(_, acc)) => {
+                    program.repair_executable().unwrap();
+                    assert_eq!(program.acc, acc, "{}", context);
+                }
+                None => assert!(program.repair_executable().is_err(), "{}", context),
+            }
+
+            let machine = Machine::new(Arc::new(exe.clone()));
+            let parallel: Vec<_> = machine
+                .repairs(4)
+                .into_iter()
+                .map(|repaired| (repaired.addr, repaired.acc))
+                .collect();
+            assert_eq!(parallel, oracle, "{}", context);
+        }
+    }
+
+    #[test]
+    fn optimizer_matches_original() {
+        for (_, exe) in sample() {
+            let optimized = optimize(&exe);
+            let (termination, acc) = run(&optimized.exe);
+
+            assert_eq!(
+                optimized.original_outcome(termination, acc),
+                run(&exe),
+                "
+{}",
+                exe
+            );
+        }
+    }
+}
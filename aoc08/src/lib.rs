@@ -1,44 +1,154 @@
 use once_cell::sync::OnceCell;
 use regex::Regex;
-use std::collections::HashSet;
+use std::collections::HashMap;
+use std::fmt;
 use std::io::BufRead;
 use std::str::FromStr;
+use thiserror::Error;
+
+mod asm;
+mod bytecode;
+mod cfg;
+mod gen;
+mod machine;
+mod optimize;
+mod profile;
+mod trace;
+
+pub use asm::{assemble, Disassembly};
+pub use bytecode::{is_bytecode, BytecodeError};
+pub use cfg::{Cfg, Repair};
+pub use gen::{Config, Generator, Shape};
+pub use machine::{Machine, Repaired};
+pub use optimize::{optimize, Optimized};
+pub use profile::Profile;
+pub use trace::{Replay, State, Step, Trace};
 
 pub type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
 
-#[derive(PartialEq)]
+/// Errors from parsing an executable. Line numbers start at 1.
+#[derive(Error, Debug)]
+pub enum ParseError {
+    #[error("line {line}: error reading input")]
+    Io {
+        line: usize,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("line {line}: unknown mnemonic in `{text}`")]
+    UnknownMnemonic { line: usize, text: String },
+    #[error("line {line}: malformed operand in `{text}`")]
+    MalformedOperand { line: usize, text: String },
+    #[error("line {line}: operand out of range in `{text}`")]
+    OperandOutOfRange { line: usize, text: String },
+    #[error("line {line}: invalid label in `{text}`")]
+    InvalidLabel { line: usize, text: String },
+    #[error("line {line}: duplicate label in `{text}`")]
+    DuplicateLabel { line: usize, text: String },
+    #[error("line {line}: undefined label in `{text}`")]
+    UndefinedLabel { line: usize, text: String },
+}
+
+#[derive(PartialEq, Clone, Debug)]
 pub struct Executable(Vec<Op>);
 
 impl Executable {
-    pub fn from_reader<R: BufRead>(reader: &mut R) -> Result<Self> {
-        let mut buffer = String::new();
+    /// Parses one `op +N` instruction per line, blank lines are skipped. Stops at the first error.
+    pub fn from_reader<R: BufRead>(reader: &mut R) -> std::result::Result<Self, ParseError> {
         let mut ops = Vec::new();
 
-        loop {
-            match reader.read_line(&mut buffer) {
-                Ok(i) if i == 0 => {
-                    break;
-                }
-                Ok(_) => {
-                    ops.push(buffer.parse()?);
-                    buffer.clear();
-                }
-                Err(_) => {
+        for (i, line) in reader.lines().enumerate() {
+            let line = line.map_err(|source| ParseError::Io {
+                line: i + 1,
+                source,
+            })?;
+
+            if let Some(op) = parse_line(i + 1, &line)? {
+                ops.push(op);
+            }
+        }
+
+        Ok(Self(ops))
+    }
+
+    /// Like `from_reader`, but carries on past bad lines and returns every error in the input.
+    /// Reading stops at the first I/O error.
+    pub fn from_reader_all<R: BufRead>(
+        reader: &mut R,
+    ) -> std::result::Result<Self, Vec<ParseError>> {
+        let mut ops = Vec::new();
+        let mut errors = Vec::new();
+
+        for (i, line) in reader.lines().enumerate() {
+            let line = match line {
+                Ok(line) => line,
+                Err(source) => {
+                    errors.push(ParseError::Io {
+                        line: i + 1,
+                        source,
+                    });
                     break;
                 }
+            };
+
+            match parse_line(i + 1, &line) {
+                Ok(Some(op)) => ops.push(op),
+                Ok(None) => {}
+                Err(e) => errors.push(e),
             }
         }
 
-        Ok(Self(ops))
+        if errors.is_empty() {
+            Ok(Self(ops))
+        } else {
+            Err(errors)
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn get(&self, addr: usize) -> Option<&Op> {
+        self.0.get(addr)
+    }
+
+    /// Replaces the instruction at `addr`, returning the instruction that was there before
+    pub fn patch(&mut self, addr: usize, op: Op) -> Result<Op> {
+        let slot = self.0.get_mut(addr).ok_or("Invalid line number")?;
+
+        Ok(std::mem::replace(slot, op))
     }
 
     fn swap_op(&mut self, line: usize) -> Result<()> {
         let op = self.0.get_mut(line).ok_or("Invalid line number")?;
 
-        match *op {
-            Op::Jmp(i) => *op = Op::Nop(i),
-            Op::Nop(i) => *op = Op::Jmp(i),
-            _ => {}
+        if let Some(swapped) = op.swapped() {
+            *op = swapped;
+        }
+
+        Ok(())
+    }
+
+    pub fn cfg(&self) -> Cfg<'_> {
+        Cfg::new(self)
+    }
+
+    /// Returns a view of the executable that can be printed with addresses and jump targets
+    pub fn disassemble(&self) -> Disassembly<'_> {
+        Disassembly::new(self)
+    }
+}
+
+/// Prints the executable in its canonical text form, one instruction per line
+impl fmt::Display for Executable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for op in self.0.iter() {
+            writeln!(f, "{}", op)?;
         }
 
         Ok(())
@@ -46,117 +156,327 @@ impl Executable {
 }
 
 #[derive(PartialEq, Clone, Debug)]
-enum Op {
+pub enum Op {
     Acc(i32),
     Jmp(i32),
     Nop(i32),
 }
 
-impl FromStr for Op {
-    type Err = Box<dyn std::error::Error>;
+impl Op {
+    /// The instruction with `jmp` and `nop` swapped, `None` for `acc`
+    pub fn swapped(&self) -> Option<Op> {
+        match *self {
+            Op::Jmp(i) => Some(Op::Nop(i)),
+            Op::Nop(i) => Some(Op::Jmp(i)),
+            Op::Acc(_) => None,
+        }
+    }
 
-    fn from_str(s: &str) -> Result<Self> {
-        static RE: OnceCell<Regex> = OnceCell::new();
-        let regex =
-            RE.get_or_init(|| Regex::new(r"(?P<instr>[a-z]{3})\s(?P<val>(\+|-)\d+)").unwrap());
+    /// Executes the instruction at `pc` in a program of `len` instructions, returning the new
+    /// program counter and accumulator
+    fn execute(
+        &self,
+        pc: usize,
+        acc: i32,
+        len: usize,
+    ) -> std::result::Result<(usize, i32), Termination> {
+        match *self {
+            Op::Acc(i) => match acc.checked_add(i) {
+                Some(acc) => Ok((pc + 1, acc)),
+                None => Err(Termination::Overflow(pc)),
+            },
+            Op::Jmp(i) => {
+                let target = pc as i64 + i as i64;
+                if target < 0 {
+                    Err(Termination::JumpBelowZero(pc))
+                } else if target > len as i64 {
+                    Err(Termination::JumpPastEnd(pc))
+                } else {
+                    Ok((target as usize, acc))
+                }
+            }
+            Op::Nop(_) => Ok((pc + 1, acc)),
+        }
+    }
+}
 
-        let caps = regex.captures(s).ok_or("invalid expression")?;
+/// Parses a single line of input, `None` if the line is blank
+fn parse_line(line: usize, text: &str) -> std::result::Result<Option<Op>, ParseError> {
+    static RE: OnceCell<Regex> = OnceCell::new();
+    let regex = RE.get_or_init(|| Regex::new(r"^(?P<instr>\S+)(\s+(?P<val>\S+))?$").unwrap());
 
-        let val = caps["val"].parse()?;
+    let text = text.trim();
+    if text.is_empty() {
+        return Ok(None);
+    }
 
-        match &caps["instr"] {
-            "acc" => Ok(Self::Acc(val)),
-            "jmp" => Ok(Self::Jmp(val)),
-            "nop" => Ok(Self::Nop(val)),
-            _ => Err("unrecognized operation".into()),
+    let malformed = || ParseError::MalformedOperand {
+        line,
+        text: text.to_string(),
+    };
+
+    let caps = regex.captures(text).ok_or_else(malformed)?;
+
+    let op: fn(i32) -> Op = match &caps["instr"] {
+        "acc" => Op::Acc,
+        "jmp" => Op::Jmp,
+        "nop" => Op::Nop,
+        _ => {
+            return Err(ParseError::UnknownMnemonic {
+                line,
+                text: text.to_string(),
+            })
         }
+    };
+
+    let val = caps.name("val").ok_or_else(malformed)?.as_str();
+
+    Ok(Some(op(parse_operand(line, text, val)?)))
+}
+
+/// Parses a numeric operand, which is a single sign followed by digits. `text` is the whole line,
+/// for the error.
+fn parse_operand(line: usize, text: &str, val: &str) -> std::result::Result<i32, ParseError> {
+    let malformed = || ParseError::MalformedOperand {
+        line,
+        text: text.to_string(),
+    };
+
+    let digits = val.strip_prefix(['+', '-']).ok_or_else(malformed)?;
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(malformed());
     }
+
+    // the operand is well formed, so the only way for parsing to fail is if it doesn't fit
+    val.parse().map_err(|_| ParseError::OperandOutOfRange {
+        line,
+        text: text.to_string(),
+    })
 }
 
-#[derive(PartialEq)]
+/// Parses a single instruction, errors are reported as being on line 1
+impl FromStr for Op {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> std::result::Result<Self, ParseError> {
+        parse_line(1, s)?.ok_or(ParseError::MalformedOperand {
+            line: 1,
+            text: String::new(),
+        })
+    }
+}
+
+impl fmt::Display for Op {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Op::Acc(i) => write!(f, "acc {:+}", i),
+            Op::Jmp(i) => write!(f, "jmp {:+}", i),
+            Op::Nop(i) => write!(f, "nop {:+}", i),
+        }
+    }
+}
+
+/// How a run of the program came to an end. Apart from `Loop` and `Eof` every outcome carries the
+/// address of the instruction that caused it, and the program counter is left pointing at it.
+#[derive(PartialEq, Clone, Copy, Debug)]
 pub enum Termination {
-    Loop,
+    /// The instruction at `start` was about to be executed for a second time, after the `len`
+    /// instructions of the cycle that leads back to it
+    Loop { start: usize, len: usize },
+    /// The program ran off the end, i.e. the program counter is exactly one past the last
+    /// instruction
     Eof,
+    /// A `jmp` tried to move the program counter below zero
+    JumpBelowZero(usize),
+    /// A `jmp` tried to move the program counter more than one past the last instruction
+    JumpPastEnd(usize),
+    /// The step budget ran out before the instruction could be executed
+    StepLimit(usize),
+    /// An `acc` overflowed the accumulator
+    Overflow(usize),
+}
+
+impl fmt::Display for Termination {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Termination::Loop { start, len } => {
+                write!(f, "infinite loop of {} instructions from {}", len, start)
+            }
+            Termination::Eof => write!(f, "end of program"),
+            Termination::JumpBelowZero(pc) => write!(f, "jump below zero at {}", pc),
+            Termination::JumpPastEnd(pc) => write!(f, "jump past the end at {}", pc),
+            Termination::StepLimit(pc) => write!(f, "step limit reached at {}", pc),
+            Termination::Overflow(pc) => write!(f, "accumulator overflow at {}", pc),
+        }
+    }
 }
 
 pub struct Program<'a> {
     exe: &'a mut Executable,
     pub acc: i32,
     pc: usize,
+    trace: Option<Trace>,
+    step_limit: Option<usize>,
 }
 
 impl<'a> Program<'a> {
     pub fn new(exe: &'a mut Executable) -> Self {
-        Self { exe, acc: 0, pc: 0 }
+        Self {
+            exe,
+            acc: 0,
+            pc: 0,
+            trace: None,
+            step_limit: None,
+        }
+    }
+
+    /// Limits the number of instructions a single call to `execute_from` may execute, `None`
+    /// removes the limit
+    pub fn set_step_limit(&mut self, limit: Option<usize>) {
+        self.step_limit = limit;
     }
 
+    /// Resets the program counter and accumulator, and clears the trace if one is being recorded
     pub fn reset(&mut self) {
         self.acc = 0;
         self.pc = 0;
+        if let Some(trace) = self.trace.as_mut() {
+            *trace = Trace::new();
+        }
     }
 
-    fn op(&mut self) {
-        match self.exe.0[self.pc] {
-            Op::Acc(i) => {
-                self.pc += 1;
-                self.acc += i;
-            }
-            Op::Jmp(i) => {
-                // Fix this
-                self.pc = (self.pc as i32 + i) as usize;
-            }
-            Op::Nop(_) => {
-                self.pc += 1;
+    /// Starts or stops recording every executed instruction. Stopping discards the trace.
+    pub fn record_trace(&mut self, record: bool) {
+        match (record, self.trace.is_some()) {
+            (true, false) => self.trace = Some(Trace::new()),
+            (false, true) => self.trace = None,
+            _ => {}
+        }
+    }
+
+    pub fn trace(&self) -> Option<&Trace> {
+        self.trace.as_ref()
+    }
+
+    /// Takes the trace recorded so far, recording continues into a fresh trace
+    pub fn take_trace(&mut self) -> Option<Trace> {
+        self.trace.as_mut().map(std::mem::take)
+    }
+
+    pub fn pc(&self) -> usize {
+        self.pc
+    }
+
+    /// The instruction that will be executed next, `None` if the program has run off the end
+    pub fn current(&self) -> Option<&Op> {
+        self.exe.get(self.pc)
+    }
+
+    pub fn executable(&self) -> &Executable {
+        self.exe
+    }
+
+    pub fn patch(&mut self, addr: usize, op: Op) -> Result<Op> {
+        self.exe.patch(addr, op)
+    }
+
+    /// Executes a single instruction. If the program has already run off the end, or the
+    /// instruction can't be executed, then nothing changes and the reason is returned instead.
+    pub fn step(&mut self) -> Option<Termination> {
+        if self.pc >= self.exe.0.len() {
+            return Some(Termination::Eof);
+        }
+
+        self.op()
+    }
+
+    fn op(&mut self) -> Option<Termination> {
+        let pc = self.pc;
+        let acc = self.acc;
+
+        match self.exe.0[pc].execute(pc, acc, self.exe.0.len()) {
+            Ok((next_pc, next_acc)) => {
+                self.pc = next_pc;
+                self.acc = next_acc;
             }
-        };
+            Err(termination) => return Some(termination),
+        }
+
+        if let Some(trace) = self.trace.as_mut() {
+            trace.push(Step {
+                pc,
+                op: self.exe.0[pc].clone(),
+                acc_before: acc,
+                acc_after: self.acc,
+                next_pc: self.pc,
+            });
+        }
+
+        None
     }
 
-    /// Executes until an instruction is repeated and then stops
+    /// Executes until an instruction is repeated and then stops. When tracing, the repeating
+    /// instructions of a `Termination::Loop` are available from `Trace::cycle`.
     pub fn execute_from(&mut self, start: usize) -> Termination {
         self.pc = start;
-        let mut executed = HashSet::new();
+        if let Some(trace) = self.trace.as_mut() {
+            trace.start_run();
+        }
+
+        // the step at which each address was executed, to measure the cycle
+        let mut executed = HashMap::new();
+        let mut steps = 0;
         let eof = self.exe.0.len();
 
         loop {
             if self.pc >= eof {
                 return Termination::Eof;
             }
-            if !executed.insert(self.pc) {
-                return Termination::Loop;
+            if let Some(first) = executed.insert(self.pc, steps) {
+                return Termination::Loop {
+                    start: self.pc,
+                    len: steps - first,
+                };
+            }
+            if self.step_limit.is_some_and(|limit| steps >= limit) {
+                return Termination::StepLimit(self.pc);
+            }
+            if let Some(termination) = self.op() {
+                return termination;
             }
-            self.op()
+            steps += 1;
         }
     }
 
+    /// Swaps the one `jmp`/`nop` that makes the program terminate and runs it from the current
+    /// program counter. The swap is found with a single pass over the control flow graph. If
+    /// several swaps would work then the one at the lowest address is used.
     pub fn repair_executable(&mut self) -> Result<()> {
-        for i in 0..self.exe.0.len() {
-            match self.exe.0[i] {
-                Op::Jmp(_) | Op::Nop(_) => {
-                    // save program counter and executable so that we don't have to resume from the
-                    // beginning
-                    let acc = self.acc;
-                    let pc = self.pc;
-
-                    // Swap and try
-                    self.exe.swap_op(i)?;
-
-                    if self.execute_from(pc) == Termination::Loop {
-                        // swap back and restore
-                        self.acc = acc;
-                        self.pc = pc;
-                        self.exe.swap_op(i)?;
-                    } else {
-                        return Ok(());
-                    }
-                }
-                _ => {}
-            }
+        let repair = Cfg::new(self.exe).repair(self.pc);
+
+        match repair {
+            Repair::Unneeded => {}
+            Repair::Swap(i) => self.exe.swap_op(i)?,
+            Repair::Ambiguous(candidates) => self.exe.swap_op(candidates[0])?,
+            Repair::Unfixable => return Err("Unfixable".into()),
         }
 
-        Err("Unfixable".into())
+        match self.execute_from(self.pc) {
+            Termination::Eof => Ok(()),
+            termination => Err(format!("Repaired executable failed: {}", termination).into()),
+        }
     }
 }
 
+/// The example program from the puzzle, for the tests of every module
+#[cfg(test)]
+pub(crate) const EXAMPLE: &str = include_str!("../input/example.txt");
+
+#[cfg(test)]
+pub(crate) fn example() -> Executable {
+    assemble(EXAMPLE).unwrap()
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -166,17 +486,7 @@ mod test {
     fn parses_executable() {
         use Op::*;
 
-        let ops = "\
-            nop +0
-            acc +1
-            jmp +4
-            acc +3
-            jmp -3
-            acc -99
-            acc +1
-            jmp -4
-            acc +6";
-        let mut ops = Cursor::new(ops);
+        let mut ops = Cursor::new(EXAMPLE);
 
         let exe = vec![
             Nop(0),
@@ -195,19 +505,7 @@ mod test {
 
     #[test]
     fn part_1() {
-        let ops = "\
-            nop +0
-            acc +1
-            jmp +4
-            acc +3
-            jmp -3
-            acc -99
-            acc +1
-            jmp -4
-            acc +6";
-        let mut ops = Cursor::new(ops);
-
-        let mut exe = Executable::from_reader(&mut ops).unwrap();
+        let mut exe = Executable::from_reader(&mut Cursor::new(EXAMPLE)).unwrap();
         let mut program = Program::new(&mut exe);
 
         program.execute_from(0);
@@ -217,22 +515,111 @@ mod test {
 
     #[test]
     fn part_2() {
-        let ops = "\
-            nop +0
-            acc +1
-            jmp +4
-            acc +3
-            jmp -3
-            acc -99
-            acc +1
-            jmp -4
-            acc +6";
-        let mut ops = Cursor::new(ops);
-
-        let mut exe = Executable::from_reader(&mut ops).unwrap();
+        let mut exe = Executable::from_reader(&mut Cursor::new(EXAMPLE)).unwrap();
         let mut program = Program::new(&mut exe);
 
         program.repair_executable().unwrap();
         assert_eq!(8, program.acc);
     }
+
+    #[test]
+    fn reports_parse_errors() {
+        let parse = |s: &str| Executable::from_reader(&mut Cursor::new(s)).unwrap_err();
+
+        assert!(matches!(
+            parse("nop +0\nmul +2"),
+            ParseError::UnknownMnemonic { line: 2, text } if text == "mul +2"
+        ));
+        assert!(matches!(
+            parse("acc 2"),
+            ParseError::MalformedOperand { line: 1, .. }
+        ));
+        assert!(matches!(
+            parse("\njmp"),
+            ParseError::MalformedOperand { line: 2, .. }
+        ));
+        assert!(matches!(
+            parse("acc +1 +2"),
+            ParseError::MalformedOperand { line: 1, .. }
+        ));
+        assert!(matches!(
+            parse("acc +2147483648"),
+            ParseError::OperandOutOfRange { line: 1, .. }
+        ));
+        assert!(Executable::from_reader(&mut Cursor::new("acc -2147483648\n\n")).is_ok());
+    }
+
+    #[test]
+    fn collects_every_parse_error() {
+        let errors =
+            Executable::from_reader_all(&mut Cursor::new("nop +0\nfoo +1\nacc +1\njmp x\n"))
+                .unwrap_err();
+
+        let lines: Vec<_> = errors
+            .iter()
+            .map(|e| match e {
+                ParseError::UnknownMnemonic { line, .. } => (*line, "mnemonic"),
+                ParseError::MalformedOperand { line, .. } => (*line, "operand"),
+                _ => unreachable!(),
+            })
+            .collect();
+
+        assert_eq!(lines, vec![(2, "mnemonic"), (4, "operand")]);
+    }
+
+    #[test]
+    fn steps_and_patches() {
+        let mut exe = Executable::from_reader(&mut Cursor::new("acc +2\njmp -1\nacc +5")).unwrap();
+        let mut program = Program::new(&mut exe);
+
+        assert_eq!(None, program.step());
+        assert_eq!((1, 2), (program.pc(), program.acc));
+        assert_eq!(Some(&Op::Jmp(-1)), program.current());
+
+        assert_eq!(Op::Jmp(-1), program.patch(1, Op::Nop(-1)).unwrap());
+        assert_eq!(None, program.step());
+        assert_eq!(None, program.step());
+        assert_eq!(Some(Termination::Eof), program.step());
+        assert_eq!((3, 7), (program.pc(), program.acc));
+        assert!(program.current().is_none());
+
+        assert!(program.patch(3, Op::Nop(0)).is_err());
+    }
+
+    fn run(source: &str) -> (Termination, usize) {
+        let mut exe = Executable::from_reader(&mut Cursor::new(source)).unwrap();
+        let mut program = Program::new(&mut exe);
+
+        let termination = program.execute_from(0);
+        (termination, program.pc())
+    }
+
+    #[test]
+    fn distinguishes_wild_jumps() {
+        assert_eq!((Termination::Eof, 2), run("nop +0\njmp +1"));
+        assert_eq!((Termination::Eof, 2), run("jmp +2\nacc +1"));
+        assert_eq!((Termination::JumpPastEnd(1), 1), run("nop +0\njmp +2"));
+        assert_eq!((Termination::JumpBelowZero(1), 1), run("acc +1\njmp -2"));
+    }
+
+    #[test]
+    fn detects_overflow() {
+        let (termination, pc) = run("acc +2147483647\nacc +0\nacc +1");
+
+        assert_eq!((Termination::Overflow(2), 2), (termination, pc));
+    }
+
+    #[test]
+    fn enforces_step_limit() {
+        let mut exe = Executable::from_reader(&mut Cursor::new("nop +0\nacc +1\nacc +1")).unwrap();
+        let mut program = Program::new(&mut exe);
+
+        program.set_step_limit(Some(2));
+        assert_eq!(Termination::StepLimit(2), program.execute_from(0));
+        assert_eq!(1, program.acc);
+
+        program.reset();
+        program.set_step_limit(Some(3));
+        assert_eq!(Termination::Eof, program.execute_from(0));
+    }
 }
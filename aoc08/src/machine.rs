@@ -0,0 +1,215 @@
+use crate::{Executable, Op, Result, Termination};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::thread;
+
+/// A program that has been repaired by swapping a single instruction
+#[derive(PartialEq, Clone, Debug)]
+pub struct Repaired {
+    pub addr: usize,
+    pub acc: i32,
+}
+
+/// Owned alternative to `Program`. The code is shared between machines and never modified, patches
+/// are kept in a separate overlay so that cloning a machine only copies its own changes.
+#[derive(Clone, Debug)]
+pub struct Machine {
+    code: Arc<Executable>,
+    patches: HashMap<usize, Op>,
+    pub acc: i32,
+    pc: usize,
+}
+
+impl Machine {
+    pub fn new(code: Arc<Executable>) -> Self {
+        Self {
+            code,
+            patches: HashMap::new(),
+            acc: 0,
+            pc: 0,
+        }
+    }
+
+    pub fn pc(&self) -> usize {
+        self.pc
+    }
+
+    /// Resets the program counter and accumulator, patches are kept
+    pub fn reset(&mut self) {
+        self.acc = 0;
+        self.pc = 0;
+    }
+
+    /// A copy of the machine that can be restored by assigning it back
+    pub fn snapshot(&self) -> Self {
+        self.clone()
+    }
+
+    pub fn len(&self) -> usize {
+        self.code.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.code.is_empty()
+    }
+
+    /// The instruction at `addr` with any patch applied
+    pub fn get(&self, addr: usize) -> Option<&Op> {
+        self.patches.get(&addr).or_else(|| self.code.get(addr))
+    }
+
+    pub fn patch(&mut self, addr: usize, op: Op) -> Result<()> {
+        if addr >= self.code.len() {
+            return Err("Invalid line number".into());
+        }
+
+        if self.code.get(addr) == Some(&op) {
+            self.patches.remove(&addr);
+        } else {
+            self.patches.insert(addr, op);
+        }
+
+        Ok(())
+    }
+
+    pub fn unpatch(&mut self, addr: usize) {
+        self.patches.remove(&addr);
+    }
+
+    /// Executes a single instruction, behaves the same as `Program::step`
+    pub fn step(&mut self) -> Option<Termination> {
+        let op = match self.get(self.pc) {
+            Some(op) => op,
+            None => return Some(Termination::Eof),
+        };
+
+        match op.execute(self.pc, self.acc, self.code.len()) {
+            Ok((pc, acc)) => {
+                self.pc = pc;
+                self.acc = acc;
+                None
+            }
+            Err(termination) => Some(termination),
+        }
+    }
+
+    /// Runs from the current program counter until the program terminates or an instruction is
+    /// about to be executed for a second time
+    pub fn run(&mut self) -> Termination {
+        // the step at which each address was executed, to measure the cycle
+        let mut executed = vec![None; self.code.len()];
+        let mut steps: usize = 0;
+
+        loop {
+            if let Some(seen) = executed.get_mut(self.pc) {
+                if let Some(first) = *seen {
+                    return Termination::Loop {
+                        start: self.pc,
+                        len: steps - first,
+                    };
+                }
+                *seen = Some(steps);
+            }
+
+            if let Some(termination) = self.step() {
+                return termination;
+            }
+            steps += 1;
+        }
+    }
+
+    /// Tries swapping every `jmp` and `nop` and running the program from the current state. The
+    /// candidates are split between `threads` threads, every swap that lets the program run off
+    /// the end is returned in address order.
+    pub fn repairs(&self, threads: usize) -> Vec<Repaired> {
+        let candidates: Vec<(usize, Op)> = (0..self.code.len())
+            .filter_map(|addr| Some((addr, self.get(addr)?.swapped()?)))
+            .collect();
+
+        if candidates.is_empty() {
+            return Vec::new();
+        }
+
+        let chunk_size = candidates.len().div_ceil(threads.max(1));
+
+        let mut repairs: Vec<Repaired> = thread::scope(|scope| {
+            let handles: Vec<_> = candidates
+                .chunks(chunk_size)
+                .map(|chunk| {
+                    scope.spawn(move || {
+                        chunk
+                            .iter()
+                            .filter_map(|(addr, swapped)| {
+                                let mut machine = self.clone();
+                                machine.patches.insert(*addr, swapped.clone());
+
+                                match machine.run() {
+                                    Termination::Eof => Some(Repaired {
+                                        addr: *addr,
+                                        acc: machine.acc,
+                                    }),
+                                    _ => None,
+                                }
+                            })
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect();
+
+            handles
+                .into_iter()
+                .flat_map(|handle| handle.join().unwrap())
+                .collect()
+        });
+
+        repairs.sort_by_key(|repaired| repaired.addr);
+        repairs
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{assemble, example};
+
+    #[test]
+    fn runs_like_program() {
+        let mut machine = Machine::new(Arc::new(example()));
+
+        assert_eq!(machine.run(), Termination::Loop { start: 1, len: 6 });
+        assert_eq!(machine.acc, 5);
+    }
+
+    #[test]
+    fn patches_do_not_touch_shared_code() {
+        let code = Arc::new(example());
+        let mut machine = Machine::new(Arc::clone(&code));
+        let snapshot = machine.snapshot();
+
+        machine.patch(7, Op::Nop(-4)).unwrap();
+        assert_eq!(machine.run(), Termination::Eof);
+        assert_eq!(machine.acc, 8);
+
+        assert_eq!(code.get(7), Some(&Op::Jmp(-4)));
+        let mut machine = snapshot;
+        assert_eq!(machine.get(7), Some(&Op::Jmp(-4)));
+        assert_eq!(machine.run(), Termination::Loop { start: 1, len: 6 });
+
+        assert!(machine.patch(9, Op::Nop(0)).is_err());
+    }
+
+    #[test]
+    fn finds_repairs_in_parallel() {
+        let machine = Machine::new(Arc::new(example()));
+
+        for threads in 1..5 {
+            assert_eq!(machine.repairs(threads), vec![Repaired { addr: 7, acc: 8 }]);
+        }
+
+        let machine = Machine::new(Arc::new(assemble("nop +3\njmp -1\nacc +1").unwrap()));
+        assert_eq!(
+            machine.repairs(2),
+            vec![Repaired { addr: 0, acc: 0 }, Repaired { addr: 1, acc: 1 }]
+        );
+    }
+}
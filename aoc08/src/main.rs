@@ -2,7 +2,7 @@ use aoc08::{Executable, Program, Result};
 use std::io;
 
 fn main() -> Result<()> {
-    let mut executable = Executable::from_reader(&mut io::stdin().lock())?;
+    let mut executable = Executable::load(&mut io::stdin().lock())?;
     let mut program = Program::new(&mut executable);
 
     // part 1
@@ -0,0 +1,270 @@
+use crate::{Cfg, Executable, Op, Termination};
+use std::collections::HashMap;
+
+/// An optimised executable along with where each of its instructions came from
+#[derive(Debug)]
+pub struct Optimized {
+    pub exe: Executable,
+    /// `origin[i]` is the address in the original program of instruction `i`. Folded `acc`
+    /// instructions map back to the first instruction of the run.
+    pub origin: Vec<usize>,
+    /// Operands of the original instructions behind each folded `acc`, keyed by new address
+    folded: HashMap<usize, Vec<i32>>,
+    original_len: usize,
+}
+
+impl Optimized {
+    /// Maps an address in the optimised program back to the original, including the address one
+    /// past the end
+    pub fn original_pc(&self, pc: usize) -> usize {
+        self.origin.get(pc).copied().unwrap_or(self.original_len)
+    }
+
+    /// Maps the result of running the optimised program back to the original program.
+    ///
+    /// Addresses carried by the termination are translated with `original_pc`, and the length of
+    /// a loop counts every instruction that was folded into it. The only other difference is an
+    /// overflow inside a folded `acc`: the original program would have got part of the way through
+    /// the run first, so the run is replayed to find the instruction that overflowed and the
+    /// accumulator at that point.
+    pub fn original_outcome(&self, termination: Termination, acc: i32) -> (Termination, i32) {
+        let termination = match termination {
+            Termination::Eof => termination,
+            Termination::Loop { start, len } => Termination::Loop {
+                start: self.original_pc(start),
+                len: self.original_cycle_len(start, len),
+            },
+            Termination::JumpBelowZero(pc) => Termination::JumpBelowZero(self.original_pc(pc)),
+            Termination::JumpPastEnd(pc) => Termination::JumpPastEnd(self.original_pc(pc)),
+            Termination::StepLimit(pc) => Termination::StepLimit(self.original_pc(pc)),
+            Termination::Overflow(pc) => match self.folded.get(&pc) {
+                Some(operands) => {
+                    let mut acc = acc;
+                    for (i, operand) in operands.iter().enumerate() {
+                        match acc.checked_add(*operand) {
+                            Some(next) => acc = next,
+                            None => return (Termination::Overflow(self.origin[pc] + i), acc),
+                        }
+                    }
+                    unreachable!("folded run overflowed but none of its instructions do")
+                }
+                None => Termination::Overflow(self.original_pc(pc)),
+            },
+        };
+
+        (termination, acc)
+    }
+
+    /// Follows the `len` instructions of a cycle in the optimised program from `start`, counting
+    /// the original instructions behind each one
+    fn original_cycle_len(&self, start: usize, len: usize) -> usize {
+        let mut pc = start;
+
+        (0..len)
+            .map(|_| {
+                let count = self.folded.get(&pc).map_or(1, Vec::len);
+                pc = match self.exe.0[pc] {
+                    Op::Jmp(i) => (pc as i64 + i as i64) as usize,
+                    _ => pc + 1,
+                };
+                count
+            })
+            .sum()
+    }
+}
+
+/// Runs the peephole passes over an executable:
+///
+/// - instructions that can't be reached from address 0 are removed
+/// - `jmp +1` becomes `nop +1`
+/// - runs of consecutive `acc` instructions are folded into one
+///
+/// Running the result gives the same `Termination` and accumulator as the original, once mapped
+/// back with `Optimized::original_outcome`. Folding only happens when nothing jumps into the middle
+/// of a run, and when the operands share a sign so that the folded instruction overflows exactly
+/// when one of the instructions in the run would have. Step limits count instructions, so they
+/// are the one thing that can differ.
+pub fn optimize(exe: &Executable) -> Optimized {
+    let len = exe.0.len();
+    let cfg = Cfg::new(exe);
+
+    let mut reachable = vec![false; len];
+    for addr in cfg.path(0) {
+        reachable[addr] = true;
+    }
+
+    // peephole rewrites, still using the original addresses
+    let ops: Vec<Op> = exe
+        .0
+        .iter()
+        .map(|op| match *op {
+            Op::Jmp(1) => Op::Nop(1),
+            ref op => op.clone(),
+        })
+        .collect();
+
+    let mut jump_target = vec![false; len + 1];
+    for (addr, op) in ops.iter().enumerate() {
+        if let (true, Op::Jmp(_)) = (reachable[addr], op) {
+            if let Some(target) = cfg.successor(addr) {
+                jump_target[target] = true;
+            }
+        }
+    }
+
+    // lay out the surviving instructions, folding acc runs as we go. Entries are the original
+    // address of the first and last instruction that went into the new one.
+    let mut kept: Vec<(usize, usize, Op)> = Vec::new();
+    let mut folded: HashMap<usize, Vec<i32>> = HashMap::new();
+    let mut new_addr = vec![None; len + 1];
+    for addr in (0..len).filter(|&addr| reachable[addr]) {
+        let new = kept.len().saturating_sub(1);
+        if let (Op::Acc(b), Some((_, last, Op::Acc(a)))) = (&ops[addr], kept.last_mut()) {
+            let adjacent = *last + 1 == addr;
+            let same_sign = (*a >= 0) == (*b >= 0);
+
+            if adjacent && same_sign && !jump_target[addr] {
+                if let Some(sum) = a.checked_add(*b) {
+                    folded.entry(new).or_insert_with(|| vec![*a]).push(*b);
+                    *a = sum;
+                    *last = addr;
+                    new_addr[addr] = Some(new);
+                    continue;
+                }
+            }
+        }
+
+        new_addr[addr] = Some(kept.len());
+        kept.push((addr, addr, ops[addr].clone()));
+    }
+    new_addr[len] = Some(kept.len());
+
+    let new_len = kept.len();
+    let origin: Vec<usize> = kept.iter().map(|(addr, _, _)| *addr).collect();
+
+    // jumps are relative, so they all need to be recalculated against the new layout
+    let ops = kept
+        .into_iter()
+        .enumerate()
+        .map(|(new, (addr, _, op))| {
+            let target = addr as i64
+                + match op {
+                    Op::Jmp(i) | Op::Nop(i) => i as i64,
+                    Op::Acc(_) => return op,
+                };
+
+            let offset = if target < 0 {
+                // still below zero
+                -(new as i64) - 1
+            } else if target > len as i64 {
+                // still past the end by more than one
+                new_len as i64 + 1 - new as i64
+            } else {
+                match new_addr[target as usize] {
+                    Some(target) => target as i64 - new as i64,
+                    // only a nop can point at removed code, and its operand doesn't matter
+                    None => 0,
+                }
+            };
+
+            match op {
+                Op::Jmp(_) => Op::Jmp(offset as i32),
+                _ => Op::Nop(offset as i32),
+            }
+        })
+        .collect();
+
+    Optimized {
+        exe: Executable(ops),
+        origin,
+        folded,
+        original_len: len,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{assemble, example, Program};
+    use std::io::Cursor;
+
+    fn run(exe: &Executable) -> (Termination, i32) {
+        let mut exe = exe.clone();
+        let mut program = Program::new(&mut exe);
+        let termination = program.execute_from(0);
+
+        (termination, program.acc)
+    }
+
+    /// Checks that the optimised program behaves the same as the original
+    fn check(exe: &Executable) -> Optimized {
+        let optimized = optimize(exe);
+        let (termination, acc) = run(&optimized.exe);
+
+        assert_eq!(
+            optimized.original_outcome(termination, acc),
+            run(exe),
+            "\n{}\noptimised to\n{}",
+            exe,
+            optimized.exe
+        );
+
+        optimized
+    }
+
+    #[test]
+    fn optimizes_example() {
+        let optimized = check(&example());
+        assert_eq!(
+            optimized.exe.to_string(),
+            "nop +0\nacc +1\njmp +3\nacc +3\njmp -3\nacc +1\njmp -3\n"
+        );
+        assert_eq!(optimized.origin, vec![0, 1, 2, 3, 4, 6, 7]);
+    }
+
+    #[test]
+    fn folds_acc_runs() {
+        let exe = assemble("acc +1\nacc +2\nacc +3\njmp +1\nacc -1\nacc +4").unwrap();
+        let optimized = check(&exe);
+
+        assert_eq!(
+            optimized.exe.to_string(),
+            "acc +6\nnop +1\nacc -1\nacc +4\n"
+        );
+        assert_eq!(optimized.origin, vec![0, 3, 4, 5]);
+    }
+
+    #[test]
+    fn does_not_fold_into_jump_targets() {
+        let exe = assemble("acc +1\ntop: acc +2\njmp top").unwrap();
+        let optimized = check(&exe);
+
+        assert_eq!(optimized.exe.len(), 3);
+    }
+
+    #[test]
+    fn keeps_faults() {
+        check(&assemble("acc +2147483647\nacc +1\nacc +1").unwrap());
+        check(&assemble("acc -2147483648\nacc -1").unwrap());
+        check(&assemble("acc +1\nacc +2147483647").unwrap());
+        check(&assemble("acc +2147483646\nacc +1\nacc +1\nacc +1").unwrap());
+        check(&assemble("jmp +2\nacc +1\njmp -3").unwrap());
+        check(&assemble("jmp +2\nacc +1\nacc +5\njmp +3\nacc +1").unwrap());
+    }
+
+    #[test]
+    fn matches_original_on_input_and_every_repair() {
+        let input = include_str!("../input/input.txt");
+        let exe = Executable::from_reader(&mut Cursor::new(input)).unwrap();
+        let optimized = check(&exe);
+        assert!(optimized.exe.len() < exe.len());
+
+        for addr in 0..exe.len() {
+            if let Some(swapped) = exe.get(addr).unwrap().swapped() {
+                let mut variant = exe.clone();
+                variant.patch(addr, swapped).unwrap();
+                check(&variant);
+            }
+        }
+    }
+}
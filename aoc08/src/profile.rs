@@ -0,0 +1,208 @@
+use crate::{Executable, Op, Termination};
+use std::io::{self, Write};
+
+/// Per instruction execution counts for a single run of a program
+pub struct Profile<'a> {
+    exe: &'a Executable,
+    /// How many times each address was executed
+    pub counts: Vec<u64>,
+    /// How many times the `jmp` at each address moved the program counter, always 0 for other
+    /// instructions
+    pub jumps_taken: Vec<u64>,
+    /// How many times a `jmp` landed on each address, which picks out the heads of loops
+    pub jumped_to: Vec<u64>,
+    /// Total amount each `acc` added to the accumulator over the run
+    pub acc_contributions: Vec<i64>,
+    pub steps: u64,
+    pub acc: i32,
+    pub termination: Termination,
+}
+
+impl<'a> Profile<'a> {
+    /// Runs the program from address 0 and counts everything it does.
+    ///
+    /// Without a step limit the run stops as soon as an instruction is about to repeat, the same
+    /// as `Program::execute_from`, so every count is at most 1. With a limit repeated instructions
+    /// are executed as normal until the program terminates or the limit is reached, which is what
+    /// shows up the hot spots of a looping program.
+    pub fn collect(exe: &'a Executable, step_limit: Option<u64>) -> Self {
+        let len = exe.0.len();
+        let mut profile = Self {
+            exe,
+            counts: vec![0; len],
+            jumps_taken: vec![0; len],
+            jumped_to: vec![0; len],
+            acc_contributions: vec![0; len],
+            steps: 0,
+            acc: 0,
+            termination: Termination::Eof,
+        };
+
+        // the step at which each address was first executed, to measure the cycle
+        let mut first_step = vec![0; len];
+        let mut pc = 0;
+        profile.termination = loop {
+            let op = match exe.0.get(pc) {
+                Some(op) => op,
+                None => break Termination::Eof,
+            };
+
+            match step_limit {
+                Some(limit) if profile.steps >= limit => break Termination::StepLimit(pc),
+                None if profile.counts[pc] > 0 => {
+                    break Termination::Loop {
+                        start: pc,
+                        len: (profile.steps - first_step[pc]) as usize,
+                    }
+                }
+                _ => {}
+            }
+
+            let (next_pc, next_acc) = match op.execute(pc, profile.acc, len) {
+                Ok(next) => next,
+                Err(termination) => break termination,
+            };
+
+            if profile.counts[pc] == 0 {
+                first_step[pc] = profile.steps;
+            }
+            profile.counts[pc] += 1;
+            profile.steps += 1;
+            match op {
+                Op::Jmp(_) => {
+                    profile.jumps_taken[pc] += 1;
+                    // a jump past the last instruction ends the program, so it lands nowhere
+                    if let Some(hits) = profile.jumped_to.get_mut(next_pc) {
+                        *hits += 1;
+                    }
+                }
+                Op::Acc(i) => profile.acc_contributions[pc] += *i as i64,
+                Op::Nop(_) => {}
+            }
+
+            pc = next_pc;
+            profile.acc = next_acc;
+        };
+
+        profile
+    }
+
+    /// Writes the program with the profile alongside each instruction
+    pub fn write_listing<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writeln!(
+            writer,
+            "{:>5}  {:>10}  {:>7}  {:>10}  {:>10}  {:>12}  instruction",
+            "addr", "count", "%", "taken", "jumped to", "acc"
+        )?;
+
+        for (addr, op) in self.exe.0.iter().enumerate() {
+            let share = if self.steps == 0 {
+                0.0
+            } else {
+                self.counts[addr] as f64 * 100.0 / self.steps as f64
+            };
+
+            writeln!(
+                writer,
+                "{:>5}  {:>10}  {:>6.2}%  {:>10}  {:>10}  {:>12}  {}",
+                addr,
+                self.counts[addr],
+                share,
+                self.jumps_taken[addr],
+                self.jumped_to[addr],
+                self.acc_contributions[addr],
+                op
+            )?;
+        }
+
+        writeln!(
+            writer,
+            "{} steps, acc {}, {}",
+            self.steps, self.acc, self.termination
+        )
+    }
+
+    /// Writes the profile as a single JSON object with one entry per instruction
+    pub fn write_json<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writeln!(
+            writer,
+            r#"{{"termination":"{}","steps":{},"acc":{},"instructions":["#,
+            self.termination, self.steps, self.acc
+        )?;
+
+        for (addr, op) in self.exe.0.iter().enumerate() {
+            let separator = if addr + 1 < self.exe.0.len() { "," } else { "" };
+
+            writeln!(
+                writer,
+                r#"  {{"addr":{},"op":"{}","count":{},"jumps_taken":{},"jumped_to":{},"acc_contribution":{}}}{}"#,
+                addr,
+                op,
+                self.counts[addr],
+                self.jumps_taken[addr],
+                self.jumped_to[addr],
+                self.acc_contributions[addr],
+                separator
+            )?;
+        }
+
+        writeln!(writer, "]}}")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::assemble;
+
+    #[test]
+    fn counts_hot_loop() {
+        let exe = assemble(
+            "\
+            top: acc +2
+            nop +0
+            jmp top",
+        )
+        .unwrap();
+
+        let profile = Profile::collect(&exe, Some(10));
+
+        assert_eq!(profile.counts, vec![4, 3, 3]);
+        assert_eq!(profile.jumps_taken, vec![0, 0, 3]);
+        assert_eq!(profile.jumped_to, vec![3, 0, 0]);
+        assert_eq!(profile.acc_contributions, vec![8, 0, 0]);
+        assert_eq!(profile.acc, 8);
+        assert_eq!(profile.termination, Termination::StepLimit(1));
+    }
+
+    #[test]
+    fn stops_at_loop_without_limit() {
+        let exe = assemble("acc +1\njmp -1").unwrap();
+        let profile = Profile::collect(&exe, None);
+
+        assert_eq!(profile.counts, vec![1, 1]);
+        assert_eq!(profile.termination, Termination::Loop { start: 0, len: 2 });
+
+        let exe = assemble("acc +1\nacc -3").unwrap();
+        let profile = Profile::collect(&exe, Some(100));
+
+        assert_eq!(profile.acc_contributions, vec![1, -3]);
+        assert_eq!(profile.termination, Termination::Eof);
+    }
+
+    #[test]
+    fn writes_json_histogram() {
+        let exe = assemble("jmp +1\nacc +1").unwrap();
+        let mut out = Vec::new();
+        Profile::collect(&exe, None).write_json(&mut out).unwrap();
+
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            r#"{"termination":"end of program","steps":2,"acc":1,"instructions":[
+  {"addr":0,"op":"jmp +1","count":1,"jumps_taken":1,"jumped_to":0,"acc_contribution":0},
+  {"addr":1,"op":"acc +1","count":1,"jumps_taken":0,"jumped_to":1,"acc_contribution":1}
+]}
+"#
+        );
+    }
+}
@@ -0,0 +1,218 @@
+use crate::Op;
+use std::io::{self, Write};
+
+/// A single executed instruction
+#[derive(PartialEq, Clone, Debug)]
+pub struct Step {
+    pub pc: usize,
+    pub op: Op,
+    pub acc_before: i32,
+    pub acc_after: i32,
+    /// Program counter after the instruction, i.e. the next instruction to be executed
+    pub next_pc: usize,
+}
+
+/// Machine state between two steps
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub struct State {
+    pub pc: usize,
+    pub acc: i32,
+}
+
+/// Every instruction executed by a program while tracing was enabled, in order
+#[derive(PartialEq, Clone, Debug, Default)]
+pub struct Trace {
+    steps: Vec<Step>,
+    /// Where the latest `Program::execute_from` started, cycles are only looked for after it
+    run_start: usize,
+}
+
+impl Trace {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn push(&mut self, step: Step) {
+        self.steps.push(step);
+    }
+
+    pub(crate) fn start_run(&mut self) {
+        self.run_start = self.steps.len();
+    }
+
+    pub fn steps(&self) -> &[Step] {
+        &self.steps
+    }
+
+    pub fn len(&self) -> usize {
+        self.steps.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+
+    /// If the last step jumps back to an instruction that has already been executed in the
+    /// latest run, returns the steps from that instruction to the end of the trace, which will
+    /// repeat forever.
+    pub fn cycle(&self) -> Option<&[Step]> {
+        let run = &self.steps[self.run_start..];
+        let last = run.last()?;
+        let start = run.iter().position(|step| step.pc == last.next_pc)?;
+
+        Some(&run[start..])
+    }
+
+    /// Writes one JSON object per step
+    pub fn write_jsonl<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        for (i, step) in self.steps.iter().enumerate() {
+            writeln!(
+                writer,
+                r#"{{"step":{},"pc":{},"op":"{}","acc_before":{},"acc_after":{},"next_pc":{}}}"#,
+                i, step.pc, step.op, step.acc_before, step.acc_after, step.next_pc
+            )?;
+        }
+
+        Ok(())
+    }
+
+    pub fn replay(&self) -> Replay<'_> {
+        Replay {
+            trace: self,
+            position: 0,
+        }
+    }
+}
+
+/// Cursor over a trace that can move in both directions. The position is the number of steps that
+/// have been executed, so position 0 is the state before the first step and position `len` is the
+/// state after the last.
+pub struct Replay<'a> {
+    trace: &'a Trace,
+    position: usize,
+}
+
+impl<'a> Replay<'a> {
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// Moves to the given position, clamped to the end of the trace
+    pub fn seek(&mut self, position: usize) {
+        self.position = position.min(self.trace.len());
+    }
+
+    /// Executes the next step, returns `None` at the end of the trace
+    pub fn forward(&mut self) -> Option<&'a Step> {
+        let step = self.trace.steps.get(self.position)?;
+        self.position += 1;
+
+        Some(step)
+    }
+
+    /// Undoes the previous step, returns `None` at the start of the trace
+    pub fn back(&mut self) -> Option<&'a Step> {
+        self.position = self.position.checked_sub(1)?;
+
+        Some(&self.trace.steps[self.position])
+    }
+
+    /// The state at the current position, `None` if the trace is empty
+    pub fn state(&self) -> Option<State> {
+        match self.trace.steps.get(self.position) {
+            Some(step) => Some(State {
+                pc: step.pc,
+                acc: step.acc_before,
+            }),
+            None => self.trace.steps.last().map(|step| State {
+                pc: step.next_pc,
+                acc: step.acc_after,
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{assemble, example, Program, Termination};
+
+    fn example_trace() -> Trace {
+        let mut exe = example();
+        let mut program = Program::new(&mut exe);
+        program.record_trace(true);
+
+        assert_eq!(
+            program.execute_from(0),
+            Termination::Loop { start: 1, len: 6 }
+        );
+        program.take_trace().unwrap()
+    }
+
+    #[test]
+    fn reports_cycle() {
+        let trace = example_trace();
+        let cycle: Vec<_> = trace.cycle().unwrap().iter().map(|step| step.pc).collect();
+
+        assert_eq!(trace.len(), 7);
+        assert_eq!(cycle, vec![1, 2, 6, 7, 3, 4]);
+    }
+
+    #[test]
+    fn cycle_only_covers_the_latest_run() {
+        let mut exe = assemble("acc +1\njmp +1\njmp -1").unwrap();
+        let mut program = Program::new(&mut exe);
+        program.record_trace(true);
+
+        let first = program.execute_from(0);
+        let second = program.execute_from(0);
+        let cycle: Vec<_> = program
+            .trace()
+            .unwrap()
+            .cycle()
+            .unwrap()
+            .iter()
+            .map(|step| step.pc)
+            .collect();
+
+        assert_eq!(first, Termination::Loop { start: 1, len: 2 });
+        assert_eq!(second, first);
+        assert_eq!(program.trace().unwrap().len(), 6);
+        assert_eq!(cycle, vec![1, 2]);
+    }
+
+    #[test]
+    fn replays_in_both_directions() {
+        let trace = example_trace();
+        let mut replay = trace.replay();
+
+        assert_eq!(replay.state(), Some(State { pc: 0, acc: 0 }));
+        assert!(replay.back().is_none());
+
+        replay.seek(5);
+        assert_eq!(replay.state(), Some(State { pc: 3, acc: 2 }));
+        assert_eq!(replay.back().unwrap().op, Op::Jmp(-4));
+        assert_eq!(replay.state(), Some(State { pc: 7, acc: 2 }));
+        assert_eq!(replay.back().unwrap().op, Op::Acc(1));
+        assert_eq!(replay.state(), Some(State { pc: 6, acc: 1 }));
+
+        replay.seek(100);
+        assert_eq!(replay.position(), 7);
+        assert_eq!(replay.state(), Some(State { pc: 1, acc: 5 }));
+        assert!(replay.forward().is_none());
+    }
+
+    #[test]
+    fn writes_json_lines() {
+        let trace = example_trace();
+        let mut out = Vec::new();
+        trace.write_jsonl(&mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+
+        assert_eq!(out.lines().count(), 7);
+        assert_eq!(
+            out.lines().nth(1).unwrap(),
+            r#"{"step":1,"pc":1,"op":"acc +1","acc_before":0,"acc_after":1,"next_pc":2}"#
+        );
+    }
+}
@@ -0,0 +1,40 @@
+use aoc09::{Config, Generator};
+use std::env;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::PathBuf;
+
+const USAGE: &str = "Usage: generate <input file> <answers file> [--len <n>] [--preamble <n>] \
+                     [--invalid <index>] [--weakness <start> <end>] [--seed <n>]";
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut paths = Vec::new();
+    let mut config = Config::default();
+    let mut seed = 0;
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or(USAGE);
+
+        match arg.as_str() {
+            "--len" => config.len = value()?.parse()?,
+            "--preamble" => config.preamble = value()?.parse()?,
+            "--invalid" => config.invalid_at = Some(value()?.parse()?),
+            "--weakness" => config.weakness = Some((value()?.parse()?, value()?.parse()?)),
+            "--seed" => seed = value()?.parse()?,
+            _ if !arg.starts_with("--") => paths.push(PathBuf::from(arg)),
+            _ => return Err(USAGE.into()),
+        }
+    }
+
+    let (input, answers) = match paths.as_slice() {
+        [input, answers] => (input, answers),
+        _ => return Err(USAGE.into()),
+    };
+
+    let puzzle = Generator::new(seed).generate(&config)?;
+    puzzle.write_input(&mut BufWriter::new(File::create(input)?))?;
+    puzzle.write_answers(&mut BufWriter::new(File::create(answers)?))?;
+
+    Ok(())
+}
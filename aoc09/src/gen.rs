@@ -0,0 +1,471 @@
+use fastrand::Rng;
+use std::collections::HashMap;
+use std::io::{self, Write};
+use thiserror::Error;
+
+/// How many times generation starts over when a planted answer turns out not to be unique
+const ATTEMPTS: usize = 100;
+
+#[derive(Error, PartialEq, Debug)]
+pub enum GenError {
+    #[error("Invalid number at {index} has to come after the preamble and before the end")]
+    InvalidPosition { index: usize },
+    #[error("Weakness range {start}..{end} must be at least two numbers before the invalid one")]
+    WeaknessRange { start: usize, end: usize },
+    #[error("A weakness range can only be planted along with an invalid number")]
+    WeaknessWithoutInvalid,
+    #[error("A preamble of {0} has no pairs to sum, valid numbers need at least 2 before them")]
+    PreambleTooShort(usize),
+    #[error("Numbers grew past i64::MAX, try a longer preamble or a shorter sequence")]
+    Overflow,
+    #[error("Couldn't plant unique answers after {0} attempts")]
+    Exhausted(usize),
+}
+
+/// Settings for generated sequences
+#[derive(Clone, Debug)]
+pub struct Config {
+    /// Number of values
+    pub len: usize,
+    pub preamble: usize,
+    /// Index of the only number that isn't the sum of two of the `preamble` numbers before it
+    pub invalid_at: Option<usize>,
+    /// Range `start..end` of at least two numbers, ending at or before the invalid number, that
+    /// is the only contiguous range adding up to it
+    pub weakness: Option<(usize, usize)>,
+    /// Preamble numbers are drawn from `1..=max_start`
+    pub max_start: i64,
+    /// Chance of a preamble number repeating an earlier one, so that the window holds pairs of
+    /// equal values that must not count as a sum
+    pub duplicate_probability: f64,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            len: 1000,
+            preamble: 25,
+            invalid_at: None,
+            weakness: None,
+            max_start: 50,
+            duplicate_probability: 0.2,
+        }
+    }
+}
+
+/// A generated sequence along with the answers to both parts
+#[derive(PartialEq, Clone, Debug)]
+pub struct Puzzle {
+    pub values: Vec<i64>,
+    pub part_1: Option<i64>,
+    pub part_2: Option<i64>,
+}
+
+impl Puzzle {
+    /// Writes the values one per line, the same format as the puzzle input
+    pub fn write_input<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        for value in self.values.iter() {
+            writeln!(writer, "{}", value)?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes the answers in the same format as the solution prints them, nothing for a part
+    /// without an answer
+    pub fn write_answers<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        for answer in self.part_1.iter().chain(self.part_2.iter()) {
+            writeln!(writer, "{}", answer)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Makes XMAS inputs with known answers, to check solutions against
+pub struct Generator {
+    rng: Rng,
+}
+
+impl Generator {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            rng: Rng::with_seed(seed),
+        }
+    }
+
+    /// Builds a sequence where every number after the preamble is the sum of two different
+    /// numbers in the window before it, apart from the planted invalid number.
+    ///
+    /// Valid numbers are built from the smaller half of the window, which keeps the growth that
+    /// the rule forces on the sequence as slow as possible. When the invalid number or the
+    /// weakness range could be confused with another answer the sequence is generated again.
+    pub fn generate(&mut self, config: &Config) -> Result<Puzzle, GenError> {
+        if let Some(index) = config.invalid_at {
+            if index < config.preamble || index >= config.len {
+                return Err(GenError::InvalidPosition { index });
+            }
+        }
+
+        // every number after the preamble apart from the invalid one has to be a sum of a pair
+        let sums = config.len.saturating_sub(config.preamble) - config.invalid_at.iter().count();
+        if config.preamble < 2 && sums > 0 {
+            return Err(GenError::PreambleTooShort(config.preamble));
+        }
+
+        match (config.weakness, config.invalid_at) {
+            (Some(_), None) => return Err(GenError::WeaknessWithoutInvalid),
+            (Some((start, end)), Some(index)) if end < start + 2 || end > index => {
+                return Err(GenError::WeaknessRange { start, end })
+            }
+            _ => {}
+        }
+
+        for _ in 0..ATTEMPTS {
+            if let Some(puzzle) = self.attempt(config)? {
+                return Ok(puzzle);
+            }
+        }
+
+        Err(GenError::Exhausted(ATTEMPTS))
+    }
+
+    /// A single go at generating the sequence, `None` if the planted answers aren't unique
+    fn attempt(&mut self, config: &Config) -> Result<Option<Puzzle>, GenError> {
+        let mut values: Vec<i64> = Vec::with_capacity(config.len);
+
+        for _ in 0..config.preamble.min(config.len) {
+            let value = if !values.is_empty() && self.rng.f64() < config.duplicate_probability {
+                values[self.rng.usize(..values.len())]
+            } else {
+                self.rng.i64(1..=config.max_start.max(1))
+            };
+            values.push(value);
+        }
+
+        let mut target = None;
+        for index in config.preamble..config.len {
+            let window = &values[index - config.preamble..index];
+
+            if Some(index) == config.invalid_at {
+                let value = match config.weakness {
+                    Some((start, end)) => values[start..end]
+                        .iter()
+                        .try_fold(0i64, |sum, &value| sum.checked_add(value))
+                        .ok_or(GenError::Overflow)?,
+                    None => {
+                        let max = window.iter().max().copied().unwrap_or(0);
+                        self.rng.i64(1..=max.saturating_mul(2).max(1))
+                    }
+                };
+
+                if is_sum(window, value) {
+                    return Ok(None);
+                }
+                target = Some(value);
+                values.push(value);
+                continue;
+            }
+
+            let value = match self.pair_sum(window) {
+                Some(value) => value?,
+                // the window is all one value, nothing can follow it
+                None => return Ok(None),
+            };
+            values.push(value);
+        }
+
+        let target = match target {
+            Some(target) => target,
+            None => {
+                return Ok(Some(Puzzle {
+                    values,
+                    part_1: None,
+                    part_2: None,
+                }))
+            }
+        };
+
+        let ranges = ranges_summing_to(&values, target);
+        let part_2 = match (config.weakness, ranges.as_slice()) {
+            (Some(planted), &[found]) if planted == found => {
+                let range = &values[planted.0..planted.1];
+                Some(range.iter().min().unwrap() + range.iter().max().unwrap())
+            }
+            (None, &[]) => None,
+            _ => return Ok(None),
+        };
+
+        Ok(Some(Puzzle {
+            values,
+            part_1: Some(target),
+            part_2,
+        }))
+    }
+
+    /// The sum of two different values from the smaller half of the window, `None` if there
+    /// aren't two different values
+    fn pair_sum(&mut self, window: &[i64]) -> Option<Result<i64, GenError>> {
+        let mut distinct = window.to_vec();
+        distinct.sort_unstable();
+        distinct.dedup();
+
+        if distinct.len() < 2 {
+            return None;
+        }
+
+        let candidates = &distinct[..(distinct.len() / 2).max(2)];
+        let a = self.rng.usize(..candidates.len());
+        let b = (a + 1 + self.rng.usize(..candidates.len() - 1)) % candidates.len();
+
+        Some(
+            candidates[a]
+                .checked_add(candidates[b])
+                .ok_or(GenError::Overflow),
+        )
+    }
+}
+
+/// Whether two different values in the window add up to `target`, done the slow way so that it
+/// doesn't share any code with the validator it's used to test
+fn is_sum(window: &[i64], target: i64) -> bool {
+    window.iter().any(|&a| {
+        window
+            .iter()
+            .any(|&b| a != b && a.checked_add(b) == Some(target))
+    })
+}
+
+/// Bounds of every range of at least two values that adds up to `target`
+fn ranges_summing_to(values: &[i64], target: i64) -> Vec<(usize, usize)> {
+    let mut starts: HashMap<i128, Vec<usize>> = HashMap::new();
+    let mut ranges = Vec::new();
+    let mut prefix = 0i128;
+
+    for end in 0..=values.len() {
+        if end > 0 {
+            prefix += values[end - 1] as i128;
+        }
+
+        if let Some(found) = starts.get(&(prefix - target as i128)) {
+            ranges.extend(
+                found
+                    .iter()
+                    .filter(|&&start| end - start >= 2)
+                    .map(|&start| (start, end)),
+            );
+        }
+        starts.entry(prefix).or_default().push(end);
+    }
+
+    ranges
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{part_1, part_2, XmasValidator};
+
+    /// Answers planted where a solver is most likely to slip: the invalid number straight after
+    /// the preamble or well past it, and the weakness at the very start, in the middle, or right
+    /// up against the invalid number. Ranges are at least three long, as a pair inside the invalid
+    /// number's window would be a sum of two of its numbers.
+    fn planted_configs() -> Vec<Config> {
+        let mut configs = Vec::new();
+
+        for &preamble in [4, 7, 12, 25].iter() {
+            for &invalid_at in [preamble + 3, 3 * preamble, 8 * preamble].iter() {
+                let middle = invalid_at / 2;
+                let ranges = [
+                    (0, 3),
+                    (middle - 2, middle + 3),
+                    (invalid_at - 4, invalid_at),
+                ];
+
+                for &weakness in ranges.iter() {
+                    configs.push(Config {
+                        len: invalid_at + preamble,
+                        preamble,
+                        invalid_at: Some(invalid_at),
+                        weakness: Some(weakness),
+                        ..Config::default()
+                    });
+                }
+            }
+        }
+
+        configs
+    }
+
+    #[test]
+    fn seed_fixes_the_sequence() {
+        let config = planted_configs()[5].clone();
+        let puzzle = Generator::new(7).generate(&config).unwrap();
+        let other = Generator::new(8).generate(&config).unwrap();
+
+        assert_eq!(Generator::new(7).generate(&config).unwrap(), puzzle);
+        // another seed plants different numbers in the same places
+        assert_ne!(other.values, puzzle.values);
+        assert_eq!(other.values.len(), puzzle.values.len());
+    }
+
+    #[test]
+    fn plain_sequences_are_valid() {
+        for preamble in 5..=25 {
+            let config = Config {
+                preamble,
+                len: 200,
+                ..Config::default()
+            };
+            let mut generator = Generator::new(preamble as u64);
+
+            for _ in 0..10 {
+                let puzzle = generator.generate(&config).unwrap();
+
+                assert_eq!(puzzle.values.len(), 200);
+                assert_eq!(part_1(&puzzle.values, preamble), None);
+            }
+        }
+    }
+
+    #[test]
+    fn solves_planted_answers() {
+        let mut generator = Generator::new(2020);
+
+        for config in planted_configs().iter().cycle().take(180) {
+            let context = format!("{:?}", config);
+            let puzzle = generator
+                .generate(config)
+                .unwrap_or_else(|e| panic!("{}: {}", context, e));
+
+            let invalid: Vec<_> =
+                XmasValidator::invalid(config.preamble, puzzle.values.iter().copied()).collect();
+            let index = config.invalid_at.unwrap();
+
+            assert_eq!(invalid[0], (index, puzzle.values[index]), "{}", context);
+            assert_eq!(part_1(&puzzle.values, config.preamble), puzzle.part_1);
+            assert_eq!(
+                part_2(&puzzle.values, puzzle.part_1.unwrap()),
+                puzzle.part_2,
+                "{}",
+                context
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_preambles_without_pairs() {
+        for preamble in 0..2 {
+            let config = Config {
+                len: 10,
+                preamble,
+                ..Config::default()
+            };
+
+            assert_eq!(
+                Generator::new(1).generate(&config),
+                Err(GenError::PreambleTooShort(preamble))
+            );
+        }
+
+        // nothing after the preamble but the invalid number, so no sums are needed
+        let config = Config {
+            len: 2,
+            preamble: 1,
+            invalid_at: Some(1),
+            ..Config::default()
+        };
+        assert!(Generator::new(1).generate(&config).is_ok());
+    }
+
+    #[test]
+    fn solves_large_input() {
+        let config = Config {
+            len: 1000,
+            preamble: 25,
+            invalid_at: Some(900),
+            weakness: Some((0, 17)),
+            ..Config::default()
+        };
+        let puzzle = Generator::new(2020).generate(&config).unwrap();
+
+        assert_eq!(part_1(&puzzle.values, 25), puzzle.part_1);
+        assert_eq!(
+            part_2(&puzzle.values, puzzle.part_1.unwrap()),
+            puzzle.part_2
+        );
+        assert!(puzzle.part_2.is_some());
+    }
+
+    #[test]
+    fn plants_invalid_number_without_weakness() {
+        let config = Config {
+            len: 300,
+            preamble: 10,
+            invalid_at: Some(150),
+            ..Config::default()
+        };
+        let puzzle = Generator::new(5).generate(&config).unwrap();
+
+        assert_eq!(part_1(&puzzle.values, 10), Some(puzzle.values[150]));
+        assert_eq!(part_2(&puzzle.values, puzzle.values[150]), None);
+        assert_eq!(puzzle.part_2, None);
+    }
+
+    #[test]
+    fn writes_input_and_answers() {
+        let puzzle = Puzzle {
+            values: vec![1, 2, 3],
+            part_1: Some(3),
+            part_2: Some(3),
+        };
+        let mut input = Vec::new();
+        let mut answers = Vec::new();
+        puzzle.write_input(&mut input).unwrap();
+        puzzle.write_answers(&mut answers).unwrap();
+
+        assert_eq!(input, b"1\n2\n3\n");
+        assert_eq!(answers, b"3\n3\n");
+    }
+
+    #[test]
+    fn rejects_bad_config() {
+        let mut generator = Generator::new(0);
+        let config = Config {
+            len: 100,
+            preamble: 25,
+            ..Config::default()
+        };
+
+        assert_eq!(
+            generator.generate(&Config {
+                invalid_at: Some(10),
+                ..config.clone()
+            }),
+            Err(GenError::InvalidPosition { index: 10 })
+        );
+        assert_eq!(
+            generator.generate(&Config {
+                invalid_at: Some(50),
+                weakness: Some((40, 51)),
+                ..config.clone()
+            }),
+            Err(GenError::WeaknessRange { start: 40, end: 51 })
+        );
+        assert_eq!(
+            generator.generate(&Config {
+                invalid_at: Some(50),
+                weakness: Some((3, 4)),
+                ..config.clone()
+            }),
+            Err(GenError::WeaknessRange { start: 3, end: 4 })
+        );
+        assert_eq!(
+            generator.generate(&Config {
+                weakness: Some((3, 5)),
+                ..config
+            }),
+            Err(GenError::WeaknessWithoutInvalid)
+        );
+    }
+}
@@ -0,0 +1,355 @@
+mod gen;
+
+pub use gen::{Config, GenError, Generator, Puzzle};
+use std::collections::{HashMap, VecDeque};
+
+/// Checks a stream of numbers against the XMAS rule: after the preamble, every number has to be
+/// the sum of two different values among the `preamble` numbers immediately before it.
+///
+/// The window is kept as a multiset that is updated as each number arrives, so nothing is rebuilt
+/// between positions.
+pub struct XmasValidator {
+    preamble: usize,
+    window: VecDeque<i64>,
+    counts: HashMap<i64, usize>,
+}
+
+impl XmasValidator {
+    pub fn new(preamble: usize) -> Self {
+        Self {
+            preamble,
+            window: VecDeque::with_capacity(preamble + 1),
+            counts: HashMap::with_capacity(preamble + 1),
+        }
+    }
+
+    /// Checks `value` against the current window and then adds it to the window. Numbers in the
+    /// preamble are always valid.
+    pub fn push(&mut self, value: i64) -> bool {
+        let valid = self.window.len() < self.preamble || self.is_sum(value);
+
+        self.window.push_back(value);
+        *self.counts.entry(value).or_insert(0) += 1;
+
+        if self.window.len() > self.preamble {
+            let old = self.window.pop_front().unwrap();
+            let count = self.counts.get_mut(&old).unwrap();
+            *count -= 1;
+            if *count == 0 {
+                self.counts.remove(&old);
+            }
+        }
+
+        valid
+    }
+
+    /// Whether two different values in the window add up to `target`
+    fn is_sum(&self, target: i64) -> bool {
+        self.counts.keys().any(|&a| {
+            let b = target - a;
+            a != b && self.counts.contains_key(&b)
+        })
+    }
+
+    /// Every invalid number in `values` along with its index
+    pub fn invalid<I: IntoIterator<Item = i64>>(
+        preamble: usize,
+        values: I,
+    ) -> Invalid<I::IntoIter> {
+        Invalid {
+            validator: Self::new(preamble),
+            values: values.into_iter().enumerate(),
+        }
+    }
+}
+
+/// Iterator over the invalid numbers of a stream, see `XmasValidator::invalid`
+pub struct Invalid<I> {
+    validator: XmasValidator,
+    values: std::iter::Enumerate<I>,
+}
+
+impl<I: Iterator<Item = i64>> Iterator for Invalid<I> {
+    type Item = (usize, i64);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let validator = &mut self.validator;
+
+        self.values.find(|&(_, value)| !validator.push(value))
+    }
+}
+
+pub fn part_1(list: &[i64], window_size: usize) -> Option<i64> {
+    XmasValidator::invalid(window_size, list.iter().copied())
+        .next()
+        .map(|(_, value)| value)
+}
+
+/// A contiguous run of numbers, `start..end`, along with a summary of its contents
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub struct Range {
+    pub start: usize,
+    pub end: usize,
+    pub sum: i64,
+    pub min: i64,
+    pub max: i64,
+}
+
+impl Range {
+    fn new(list: &[i64], start: usize, end: usize) -> Self {
+        let slice = &list[start..end];
+
+        Self {
+            start,
+            end,
+            sum: slice.iter().sum(),
+            min: *slice.iter().min().unwrap(),
+            max: *slice.iter().max().unwrap(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+}
+
+/// Every contiguous run of at least `min_len` numbers (and at least one) that adds up to
+/// `target`, ordered by start and then end.
+///
+/// Without negative numbers this is a two pointer sweep, otherwise prefix sums are looked up in a
+/// hash map. Either way it's linear in the length of the list plus the total length of the ranges
+/// returned, which is what building the summaries costs.
+pub fn contiguous_sums(list: &[i64], target: i64, min_len: usize) -> Vec<Range> {
+    let min_len = min_len.max(1);
+
+    let mut bounds = if list.iter().all(|&value| value >= 0) {
+        sweep(list, target, min_len)
+    } else {
+        prefix_lookup(list, target, min_len)
+    };
+    bounds.sort_unstable();
+
+    bounds
+        .into_iter()
+        .map(|(start, end)| Range::new(list, start, end))
+        .collect()
+}
+
+/// Two pointers for non-negative lists. For each end, once the window sum is no longer above the
+/// target, the only other starts that give the same sum are the ones skipping leading zeros.
+fn sweep(list: &[i64], target: i64, min_len: usize) -> Vec<(usize, usize)> {
+    let mut bounds = Vec::new();
+    let mut start = 0;
+    let mut sum = 0;
+
+    for end in 1..=list.len() {
+        sum += list[end - 1];
+
+        while start < end && sum > target {
+            sum -= list[start];
+            start += 1;
+        }
+
+        if sum != target {
+            continue;
+        }
+
+        let mut s = start;
+        while s < end && end - s >= min_len {
+            bounds.push((s, end));
+
+            if list[s] != 0 {
+                break;
+            }
+            s += 1;
+        }
+    }
+
+    bounds
+}
+
+/// Prefix sums for lists with negative numbers, `list[i..j]` sums to the target exactly when
+/// `prefix[j] - prefix[i] == target`
+fn prefix_lookup(list: &[i64], target: i64, min_len: usize) -> Vec<(usize, usize)> {
+    let mut bounds = Vec::new();
+    let mut seen: HashMap<i64, Vec<usize>> = HashMap::new();
+    let mut prefix = 0;
+
+    for j in 0..=list.len() {
+        if j > 0 {
+            prefix += list[j - 1];
+        }
+
+        if let Some(starts) = seen.get(&(prefix - target)) {
+            bounds.extend(
+                starts
+                    .iter()
+                    .take_while(|&&i| j - i >= min_len)
+                    .map(|&i| (i, j)),
+            );
+        }
+
+        seen.entry(prefix).or_default().push(j);
+    }
+
+    bounds
+}
+
+/// Finds the first contiguous run of at least two numbers that adds up to `target`, and returns
+/// the sum of its smallest and largest numbers
+pub fn part_2(list: &[i64], target: i64) -> Option<i64> {
+    contiguous_sums(list, target, 2)
+        .first()
+        .map(|range| range.min + range.max)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_1() {
+        let list = vec![
+            35, 20, 15, 25, 47, 40, 62, 55, 65, 95, 102, 117, 150, 182, 127, 219, 299, 277, 309,
+            576,
+        ];
+
+        assert_eq!(part_1(&list, 5), Some(127));
+    }
+
+    #[test]
+    fn test_2() {
+        let list = vec![
+            35, 20, 15, 25, 47, 40, 62, 55, 65, 95, 102, 117, 150, 182, 127, 219, 299, 277, 309,
+            576,
+        ];
+
+        assert_eq!(part_2(&list, 127), Some(62));
+    }
+
+    #[test]
+    fn finds_range_at_start() {
+        let list = vec![3, 4, 10, 1];
+
+        assert_eq!(part_2(&list, 7), Some(7));
+        assert_eq!(part_2(&list, 11), Some(11));
+        assert_eq!(part_2(&list, 10), None);
+    }
+
+    #[test]
+    fn finds_every_range() {
+        let list = vec![1, 2, 3, 0, 3, 2, 1];
+        let ranges: Vec<_> = contiguous_sums(&list, 6, 1)
+            .into_iter()
+            .map(|range| (range.start, range.end))
+            .collect();
+
+        assert_eq!(ranges, vec![(0, 3), (0, 4), (2, 5), (3, 7), (4, 7)]);
+
+        let range = contiguous_sums(&list, 6, 4)[0];
+        assert_eq!(
+            range,
+            Range {
+                start: 0,
+                end: 4,
+                sum: 6,
+                min: 0,
+                max: 3
+            }
+        );
+    }
+
+    #[test]
+    fn handles_zero_target_and_zeros() {
+        let ranges: Vec<_> = contiguous_sums(&[0, 0, 0], 0, 2)
+            .into_iter()
+            .map(|range| (range.start, range.end))
+            .collect();
+
+        assert_eq!(ranges, vec![(0, 2), (0, 3), (1, 3)]);
+        assert!(contiguous_sums(&[], 0, 1).is_empty());
+    }
+
+    #[test]
+    fn handles_negative_numbers() {
+        let list = vec![4, -2, 3, -1, 2, -4];
+        let ranges: Vec<_> = contiguous_sums(&list, 2, 1)
+            .into_iter()
+            .map(|range| (range.start, range.end, range.min, range.max))
+            .collect();
+
+        assert_eq!(
+            ranges,
+            vec![
+                (0, 2, -2, 4),
+                (0, 6, -4, 4),
+                (1, 5, -2, 3),
+                (2, 4, -1, 3),
+                (4, 5, 2, 2)
+            ]
+        );
+    }
+
+    #[test]
+    fn strategies_agree() {
+        // both strategies work on non-negative lists, so they can be checked against each other
+        let list = vec![5, 0, 2, 3, 0, 0, 5, 1, 4, 0, 5];
+        for target in 0..12 {
+            for min_len in 1..4 {
+                let mut sweep = sweep(&list, target, min_len);
+                let mut lookup = prefix_lookup(&list, target, min_len);
+                sweep.sort_unstable();
+                lookup.sort_unstable();
+
+                assert_eq!(sweep, lookup, "target {} min_len {}", target, min_len);
+            }
+        }
+    }
+
+    #[test]
+    fn finds_every_invalid_number() {
+        let list = vec![
+            35, 20, 15, 25, 47, 40, 62, 55, 65, 95, 102, 117, 150, 182, 127, 219, 299, 277, 309,
+            576,
+        ];
+
+        let invalid: Vec<_> = XmasValidator::invalid(5, list).collect();
+
+        assert_eq!(invalid, vec![(14, 127)]);
+    }
+
+    #[test]
+    fn pairs_need_different_values() {
+        let mut validator = XmasValidator::new(2);
+
+        assert!(validator.push(5));
+        assert!(validator.push(5));
+        // 5 + 5 doesn't count
+        assert!(!validator.push(10));
+        // the window is now [5, 10], then [10, 15] and [15, 25]
+        assert!(validator.push(15));
+        assert!(validator.push(25));
+        assert!(!validator.push(30));
+
+        // a duplicate in the window doesn't stop the other values pairing up with it
+        let mut validator = XmasValidator::new(3);
+        for &value in [5, 5, 10].iter() {
+            assert!(validator.push(value));
+        }
+        assert!(validator.push(15));
+
+        let invalid: Vec<_> = XmasValidator::invalid(1, vec![1, 2, 3, 4]).collect();
+        assert_eq!(invalid, vec![(1, 2), (2, 3), (3, 4)]);
+    }
+
+    #[test]
+    fn empty_preamble_rejects_everything() {
+        let invalid: Vec<_> = XmasValidator::invalid(0, vec![1, 2]).collect();
+
+        assert_eq!(invalid, vec![(0, 1), (1, 2)]);
+    }
+}
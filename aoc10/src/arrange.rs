@@ -0,0 +1,551 @@
+use crate::{AdapterChain, ChainError};
+use fastrand::Rng;
+use num_bigint::BigUint;
+use num_traits::{One, Zero};
+use std::collections::HashSet;
+
+/// Limits on which arrangements count. The default allows every arrangement.
+#[derive(Clone, Debug)]
+pub struct Constraints {
+    /// Joltages of adapters that every arrangement has to use. An adapter that isn't in the bag
+    /// can't be used, so requiring one leaves no arrangements.
+    pub required: Vec<u32>,
+    /// Joltages of adapters that no arrangement may use
+    pub forbidden: Vec<u32>,
+    /// Largest number of adapters an arrangement may use, the outlet and device don't count
+    pub max_adapters: Option<usize>,
+    /// Smallest number of steps of exactly `hop_gap` jolts, including the step to the device
+    pub min_hops: usize,
+    pub hop_gap: u32,
+}
+
+impl Default for Constraints {
+    fn default() -> Self {
+        Self {
+            required: Vec::new(),
+            forbidden: Vec::new(),
+            max_adapters: None,
+            min_hops: 0,
+            hop_gap: 3,
+        }
+    }
+}
+
+/// Where an arrangement has got to: a position in the sorted joltages, how many more adapters it
+/// may use, and how many more hops it still needs
+#[derive(Clone, Copy, Debug)]
+struct State {
+    pos: usize,
+    budget: usize,
+    hops: usize,
+}
+
+/// Every distinct way of connecting the outlet to the device with some of the adapters, where
+/// each step has to be one of the differences the `AdapterChain` allows.
+///
+/// Arrangements are sequences of joltages starting with the outlet and ending with the device,
+/// and are ordered lexicographically. Counts grow exponentially with the number of adapters, so
+/// they are kept as arbitrary precision integers.
+#[derive(Clone, Debug)]
+pub struct Arrangements {
+    joltages: Vec<u32>,
+    /// `steps[i]` holds every position one allowed step on from `i`, in ascending order
+    steps: Vec<Vec<usize>>,
+    hop_gap: u32,
+    /// Whether the number of adapters is limited, without a limit the budget is always 0
+    limited: bool,
+    budgets: usize,
+    hops: usize,
+    /// Number of arrangements from each state to the device, see `Arrangements::index`
+    ways: Vec<BigUint>,
+}
+
+impl Arrangements {
+    pub fn new(chain: &AdapterChain, adapters: &[u32]) -> Result<Self, ChainError> {
+        Self::with_constraints(chain, adapters, &Constraints::default())
+    }
+
+    /// Counts the arrangements with a dynamic program over the sorted joltages, working back from
+    /// the device to the outlet. Each position only looks as far ahead as the largest allowed
+    /// difference, and alongside the position the program tracks the remaining adapter budget and
+    /// the hops still needed, but only when those constraints are set. Fails only if the device
+    /// rating doesn't fit in a `u32`.
+    pub fn with_constraints(
+        chain: &AdapterChain,
+        adapters: &[u32],
+        constraints: &Constraints,
+    ) -> Result<Self, ChainError> {
+        let joltages = chain.joltages(adapters)?;
+        let len = joltages.len();
+        let max_gap = chain.allowed().last().copied().unwrap_or(0);
+
+        let required: HashSet<u32> = constraints.required.iter().copied().collect();
+        let forbidden: HashSet<u32> = constraints.forbidden.iter().copied().collect();
+        let missing = required.iter().any(|joltage| !adapters.contains(joltage));
+
+        // positions of required adapters can't be stepped over, forbidden ones can't be stepped on
+        let mut steps = vec![Vec::new(); len];
+        if !missing {
+            for i in 0..len - 1 {
+                for j in i + 1..len {
+                    let gap = joltages[j] - joltages[i];
+                    if gap > max_gap {
+                        break;
+                    }
+
+                    let usable = j == len - 1 || !forbidden.contains(&joltages[j]);
+                    if usable && chain.allows(gap) {
+                        steps[i].push(j);
+                    }
+                    if j < len - 1 && required.contains(&joltages[j]) {
+                        break;
+                    }
+                }
+            }
+        }
+
+        let mut arrangements = Self {
+            joltages,
+            steps,
+            hop_gap: constraints.hop_gap,
+            limited: constraints.max_adapters.is_some(),
+            budgets: constraints.max_adapters.map_or(1, |max| max.min(len) + 1),
+            // an arrangement has at most len - 1 steps, so anything more is as unreachable as len
+            hops: constraints.min_hops.min(len) + 1,
+            ways: Vec::new(),
+        };
+        arrangements.ways = vec![BigUint::zero(); len * arrangements.budgets * arrangements.hops];
+
+        for budget in 0..arrangements.budgets {
+            let device = arrangements.index(State {
+                pos: len - 1,
+                budget,
+                hops: 0,
+            });
+            arrangements.ways[device] = BigUint::one();
+        }
+
+        for pos in (0..len - 1).rev() {
+            for budget in 0..arrangements.budgets {
+                for hops in 0..arrangements.hops {
+                    let state = State { pos, budget, hops };
+                    let total = arrangements
+                        .successors(state)
+                        .fold(BigUint::zero(), |total, next| {
+                            total + &arrangements.ways[arrangements.index(next)]
+                        });
+
+                    let index = arrangements.index(state);
+                    arrangements.ways[index] = total;
+                }
+            }
+        }
+
+        Ok(arrangements)
+    }
+
+    fn index(&self, state: State) -> usize {
+        (state.pos * self.budgets + state.budget) * self.hops + state.hops
+    }
+
+    fn start(&self) -> State {
+        State {
+            pos: 0,
+            budget: self.budgets - 1,
+            hops: self.hops - 1,
+        }
+    }
+
+    fn ways(&self, state: State) -> &BigUint {
+        &self.ways[self.index(state)]
+    }
+
+    /// The state after each allowed step from `state` that stays within the adapter budget, in
+    /// ascending order of joltage. Some of them may not be able to reach the device.
+    fn successors(&self, state: State) -> impl Iterator<Item = State> + '_ {
+        let device = self.joltages.len() - 1;
+
+        self.steps[state.pos].iter().filter_map(move |&pos| {
+            let budget = if self.limited && pos != device {
+                state.budget.checked_sub(1)?
+            } else {
+                state.budget
+            };
+            let hop = self.joltages[pos] - self.joltages[state.pos] == self.hop_gap;
+            let hops = state.hops.saturating_sub(hop as usize);
+
+            Some(State { pos, budget, hops })
+        })
+    }
+
+    /// Successors that lead on to the device
+    fn live_successors(&self, state: State) -> impl Iterator<Item = State> + '_ {
+        self.successors(state)
+            .filter(move |&next| !self.ways(next).is_zero())
+    }
+
+    /// Total number of arrangements, 0 when the device can't be reached at all
+    pub fn count(&self) -> &BigUint {
+        self.ways(self.start())
+    }
+
+    /// Lazily walks every arrangement in lexicographic order. Dead ends are never visited, so
+    /// each arrangement takes time proportional to its length.
+    pub fn iter(&self) -> Iter<'_> {
+        let stack = if self.count().is_zero() {
+            Vec::new()
+        } else {
+            vec![(self.start(), 0)]
+        };
+
+        Iter {
+            arrangements: self,
+            stack,
+        }
+    }
+
+    /// The arrangement at index `k` in lexicographic order, counting from 0
+    pub fn nth(&self, k: &BigUint) -> Option<Vec<u32>> {
+        if k >= self.count() {
+            return None;
+        }
+
+        let mut k = k.clone();
+        let mut state = self.start();
+        let mut arrangement = vec![self.joltages[0]];
+
+        while state.pos + 1 < self.joltages.len() {
+            for next in self.live_successors(state) {
+                let ways = self.ways(next);
+                if k < *ways {
+                    state = next;
+                    break;
+                }
+                k -= ways;
+            }
+
+            arrangement.push(self.joltages[state.pos]);
+        }
+
+        Some(arrangement)
+    }
+
+    /// An endless stream of arrangements, each one equally likely to come up
+    pub fn sampler(&self, seed: u64) -> Sampler<'_> {
+        Sampler {
+            arrangements: self,
+            rng: Rng::with_seed(seed),
+        }
+    }
+}
+
+/// Iterator over every arrangement, see `Arrangements::iter`
+pub struct Iter<'a> {
+    arrangements: &'a Arrangements,
+    /// States along the current path, each with how many of its successors have been tried
+    stack: Vec<(State, usize)>,
+}
+
+impl Iterator for Iter<'_> {
+    type Item = Vec<u32>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let arrangements = self.arrangements;
+        let device = arrangements.joltages.len() - 1;
+
+        loop {
+            let (state, tried) = self.stack.last_mut()?;
+
+            if state.pos == device {
+                let arrangement = self
+                    .stack
+                    .iter()
+                    .map(|(state, _)| arrangements.joltages[state.pos])
+                    .collect();
+                self.stack.pop();
+                return Some(arrangement);
+            }
+
+            match arrangements.live_successors(*state).nth(*tried) {
+                Some(next) => {
+                    *tried += 1;
+                    self.stack.push((next, 0));
+                }
+                None => {
+                    self.stack.pop();
+                }
+            }
+        }
+    }
+}
+
+/// Uniformly random arrangements, see `Arrangements::sampler`
+pub struct Sampler<'a> {
+    arrangements: &'a Arrangements,
+    rng: Rng,
+}
+
+impl Iterator for Sampler<'_> {
+    type Item = Vec<u32>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let count = self.arrangements.count();
+        if count.is_zero() {
+            return None;
+        }
+
+        let k = self.below(count);
+        self.arrangements.nth(&k)
+    }
+}
+
+impl Sampler<'_> {
+    /// A number in `0..bound`, by drawing just enough random bits and trying again whenever the
+    /// result is too large. `bound` must not be 0.
+    fn below(&mut self, bound: &BigUint) -> BigUint {
+        let bits = bound.bits();
+        let digits = bits.div_ceil(32) as usize;
+        let spare = digits as u64 * 32 - bits;
+
+        loop {
+            let mut digits: Vec<u32> = (0..digits).map(|_| self.rng.u32(..)).collect();
+            if let Some(top) = digits.last_mut() {
+                *top >>= spare;
+            }
+
+            let candidate = BigUint::new(digits);
+            if &candidate < bound {
+                return candidate;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn example() -> Arrangements {
+        let input = vec![16, 10, 15, 5, 1, 11, 7, 19, 6, 12, 4];
+
+        Arrangements::new(&AdapterChain::default(), &input).unwrap()
+    }
+
+    #[test]
+    fn part_2() {
+        assert_eq!(example().count(), &BigUint::from(8u32));
+    }
+
+    #[test]
+    fn part_2_alt() {
+        let input = vec![
+            28, 33, 18, 42, 31, 14, 46, 20, 48, 47, 24, 23, 49, 45, 19, 38, 39, 11, 1, 32, 25, 35,
+            8, 17, 7, 9, 4, 2, 34, 10, 3,
+        ];
+        let arrangements = Arrangements::new(&AdapterChain::default(), &input).unwrap();
+
+        assert_eq!(arrangements.count(), &BigUint::from(19208u32));
+        assert_eq!(arrangements.iter().count(), 19208);
+    }
+
+    #[test]
+    fn counts_past_u64() {
+        let input: Vec<u32> = (1..=300).collect();
+        let arrangements = Arrangements::new(&AdapterChain::default(), &input).unwrap();
+
+        // every joltage is present, so the counts follow the tribonacci numbers
+        let mut expected = [BigUint::zero(), BigUint::zero(), BigUint::one()];
+        for _ in 0..300 {
+            let next = &expected[0] + &expected[1] + &expected[2];
+            expected = [expected[1].clone(), expected[2].clone(), next];
+        }
+
+        assert_eq!(arrangements.count(), &expected[2]);
+        assert!(arrangements.count() > &BigUint::from(u64::MAX));
+    }
+
+    #[test]
+    fn iterates_in_lexicographic_order() {
+        let arrangements = example();
+        let all: Vec<_> = arrangements.iter().collect();
+
+        assert_eq!(all.len(), 8);
+        assert_eq!(all[0], vec![0, 1, 4, 5, 6, 7, 10, 11, 12, 15, 16, 19, 22]);
+        assert_eq!(all[7], vec![0, 1, 4, 7, 10, 12, 15, 16, 19, 22]);
+        assert!(all.windows(2).all(|pair| pair[0] < pair[1]));
+
+        for (k, arrangement) in all.iter().enumerate() {
+            assert_eq!(
+                arrangements.nth(&BigUint::from(k)).as_ref(),
+                Some(arrangement)
+            );
+        }
+        assert_eq!(arrangements.nth(&BigUint::from(8u32)), None);
+    }
+
+    #[test]
+    fn samples_uniformly() {
+        let arrangements = example();
+        let all: Vec<_> = arrangements.iter().collect();
+        let mut counts = vec![0; all.len()];
+
+        for sample in arrangements.sampler(10).take(8000) {
+            counts[all.iter().position(|a| *a == sample).unwrap()] += 1;
+        }
+
+        assert!(
+            counts.iter().all(|&count| (850..1150).contains(&count)),
+            "{:?}",
+            counts
+        );
+    }
+
+    #[test]
+    fn follows_allowed_differences() {
+        let chain = AdapterChain::new(vec![1, 2, 5]).device_gap(5);
+        let arrangements = Arrangements::new(&chain, &[1, 3, 4, 6]).unwrap();
+        let all: Vec<_> = arrangements.iter().collect();
+
+        assert_eq!(all, vec![vec![0, 1, 3, 4, 6, 11], vec![0, 1, 6, 11]]);
+    }
+
+    /// The slow way, every unconstrained arrangement that satisfies the constraints
+    fn filtered(input: &[u32], constraints: &Constraints) -> Vec<Vec<u32>> {
+        Arrangements::new(&AdapterChain::default(), input)
+            .unwrap()
+            .iter()
+            .filter(|arrangement| {
+                let adapters = &arrangement[1..arrangement.len() - 1];
+                let hops = arrangement
+                    .windows(2)
+                    .filter(|pair| pair[1] - pair[0] == constraints.hop_gap)
+                    .count();
+
+                constraints.required.iter().all(|j| adapters.contains(j))
+                    && !constraints.forbidden.iter().any(|j| adapters.contains(j))
+                    && constraints
+                        .max_adapters
+                        .is_none_or(|max| adapters.len() <= max)
+                    && hops >= constraints.min_hops
+            })
+            .collect()
+    }
+
+    #[test]
+    fn constrained_matches_filtered() {
+        let input = vec![
+            28, 33, 18, 42, 31, 14, 46, 20, 48, 47, 24, 23, 49, 45, 19, 38, 39, 11, 1, 32, 25, 35,
+            8, 17, 7, 9, 4, 2, 34, 10, 3,
+        ];
+        let cases = vec![
+            Constraints::default(),
+            Constraints {
+                required: vec![2, 9],
+                ..Constraints::default()
+            },
+            Constraints {
+                forbidden: vec![1, 24, 47],
+                ..Constraints::default()
+            },
+            Constraints {
+                max_adapters: Some(22),
+                ..Constraints::default()
+            },
+            Constraints {
+                min_hops: 12,
+                ..Constraints::default()
+            },
+            Constraints {
+                required: vec![8, 48],
+                forbidden: vec![9, 32],
+                max_adapters: Some(24),
+                min_hops: 11,
+                hop_gap: 3,
+            },
+            Constraints {
+                min_hops: 3,
+                hop_gap: 2,
+                ..Constraints::default()
+            },
+        ];
+
+        for constraints in cases {
+            let arrangements =
+                Arrangements::with_constraints(&AdapterChain::default(), &input, &constraints)
+                    .unwrap();
+            let expected = filtered(&input, &constraints);
+            let all: Vec<_> = arrangements.iter().collect();
+
+            assert!(!expected.is_empty(), "{:?}", constraints);
+            assert_eq!(all, expected, "{:?}", constraints);
+            assert_eq!(arrangements.count(), &BigUint::from(expected.len()));
+
+            let last = BigUint::from(expected.len() - 1);
+            assert_eq!(arrangements.nth(&last).as_ref(), expected.last());
+            for sample in arrangements.sampler(1).take(20) {
+                assert!(expected.contains(&sample), "{:?}", constraints);
+            }
+        }
+    }
+
+    #[test]
+    fn impossible_constraints() {
+        let input = vec![16, 10, 15, 5, 1, 11, 7, 19, 6, 12, 4];
+        let count = |constraints: Constraints| {
+            Arrangements::with_constraints(&AdapterChain::default(), &input, &constraints)
+                .unwrap()
+                .count()
+                .clone()
+        };
+
+        // 4 can't be skipped
+        assert!(count(Constraints {
+            forbidden: vec![4],
+            ..Constraints::default()
+        })
+        .is_zero());
+        assert!(count(Constraints {
+            required: vec![2],
+            ..Constraints::default()
+        })
+        .is_zero());
+        assert!(count(Constraints {
+            max_adapters: Some(7),
+            ..Constraints::default()
+        })
+        .is_zero());
+        assert_eq!(
+            count(Constraints {
+                max_adapters: Some(8),
+                ..Constraints::default()
+            }),
+            BigUint::one()
+        );
+        // more hops than there are steps
+        assert!(count(Constraints {
+            min_hops: input.len() + 2,
+            ..Constraints::default()
+        })
+        .is_zero());
+        assert!(count(Constraints {
+            min_hops: usize::MAX,
+            ..Constraints::default()
+        })
+        .is_zero());
+    }
+
+    #[test]
+    fn no_arrangements_past_a_gap() {
+        let arrangements = Arrangements::new(&AdapterChain::default(), &[1, 2, 6]).unwrap();
+
+        assert!(arrangements.count().is_zero());
+        assert_eq!(arrangements.iter().next(), None);
+        assert_eq!(arrangements.nth(&BigUint::zero()), None);
+        assert_eq!(arrangements.sampler(0).next(), None);
+
+        assert!(matches!(
+            Arrangements::new(&AdapterChain::default(), &[u32::MAX]),
+            Err(ChainError::DeviceOverflow { .. })
+        ));
+    }
+}
@@ -0,0 +1,213 @@
+use std::collections::BTreeMap;
+use thiserror::Error;
+
+#[derive(Error, PartialEq, Debug)]
+pub enum ChainError {
+    #[error("Gap of {gap} jolts from {from} to {to} at position {position} isn't allowed")]
+    Unbridgeable {
+        /// Position in the sorted chain of the joltage that can't be reached, the outlet is
+        /// position 0 and the device comes last
+        position: usize,
+        from: u32,
+        to: u32,
+        gap: u32,
+    },
+    #[error("Device would be rated {gap} jolts above {highest}, past the largest joltage")]
+    DeviceOverflow { highest: u32, gap: u32 },
+}
+
+/// Plans a chain that uses every adapter, from the outlet at 0 jolts up to the device.
+///
+/// Each step in the chain has to be one of the allowed differences, the puzzle allows 1, 2 and 3
+/// and rates the device 3 jolts above the highest adapter.
+#[derive(Clone, Debug)]
+pub struct AdapterChain {
+    allowed: Vec<u32>,
+    device_gap: u32,
+}
+
+impl Default for AdapterChain {
+    fn default() -> Self {
+        Self::new(vec![1, 2, 3])
+    }
+}
+
+impl AdapterChain {
+    pub fn new<I: IntoIterator<Item = u32>>(allowed: I) -> Self {
+        let mut allowed: Vec<u32> = allowed.into_iter().collect();
+        allowed.sort_unstable();
+        allowed.dedup();
+
+        Self {
+            allowed,
+            device_gap: 3,
+        }
+    }
+
+    /// How far above the highest adapter the device is rated, 3 by default
+    pub fn device_gap(mut self, gap: u32) -> Self {
+        self.device_gap = gap;
+        self
+    }
+
+    pub fn allowed(&self) -> &[u32] {
+        &self.allowed
+    }
+
+    /// Whether a single step of `gap` jolts is allowed
+    pub fn allows(&self, gap: u32) -> bool {
+        self.allowed.binary_search(&gap).is_ok()
+    }
+
+    /// The outlet, the adapters in order, and then the device
+    pub(crate) fn joltages(&self, adapters: &[u32]) -> Result<Vec<u32>, ChainError> {
+        let mut joltages = Vec::with_capacity(adapters.len() + 2);
+        joltages.push(0);
+        joltages.extend_from_slice(adapters);
+        joltages.sort_unstable();
+
+        let highest = joltages[joltages.len() - 1];
+        let device = highest
+            .checked_add(self.device_gap)
+            .ok_or(ChainError::DeviceOverflow {
+                highest,
+                gap: self.device_gap,
+            })?;
+        joltages.push(device);
+
+        Ok(joltages)
+    }
+
+    /// Sorts the adapters into a chain, failing at the first gap that isn't allowed. The gap to
+    /// the device is checked the same as the others.
+    pub fn plan(&self, adapters: &[u32]) -> Result<Chain, ChainError> {
+        let joltages = self.joltages(adapters)?;
+
+        let mut gaps = BTreeMap::new();
+        for (position, pair) in joltages.windows(2).enumerate() {
+            let (from, to) = (pair[0], pair[1]);
+            let gap = to - from;
+
+            if !self.allows(gap) {
+                return Err(ChainError::Unbridgeable {
+                    position: position + 1,
+                    from,
+                    to,
+                    gap,
+                });
+            }
+            *gaps.entry(gap).or_insert(0) += 1;
+        }
+
+        Ok(Chain { joltages, gaps })
+    }
+}
+
+/// A chain through every adapter, see `AdapterChain::plan`
+#[derive(PartialEq, Clone, Debug)]
+pub struct Chain {
+    /// Every joltage along the chain in order, starting with the outlet and ending with the device
+    pub joltages: Vec<u32>,
+    /// How many steps of each size the chain takes
+    pub gaps: BTreeMap<u32, usize>,
+}
+
+impl Chain {
+    /// How many steps of exactly `gap` jolts the chain takes
+    pub fn count(&self, gap: u32) -> usize {
+        self.gaps.get(&gap).copied().unwrap_or(0)
+    }
+
+    pub fn device(&self) -> u32 {
+        self.joltages[self.joltages.len() - 1]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn part_1() {
+        let input = vec![16, 10, 15, 5, 1, 11, 7, 19, 6, 12, 4];
+        let chain = AdapterChain::default().plan(&input).unwrap();
+
+        assert_eq!(chain.count(1), 7);
+        assert_eq!(chain.count(3), 5);
+        assert_eq!(chain.device(), 22);
+    }
+
+    #[test]
+    fn full_histogram() {
+        let chain = AdapterChain::new(vec![5, 2, 1])
+            .device_gap(5)
+            .plan(&[2, 3, 8, 10])
+            .unwrap();
+
+        assert_eq!(chain.joltages, vec![0, 2, 3, 8, 10, 15]);
+        assert_eq!(
+            chain.gaps.into_iter().collect::<Vec<_>>(),
+            vec![(1, 1), (2, 2), (5, 2)]
+        );
+    }
+
+    #[test]
+    fn names_unbridgeable_gap() {
+        assert_eq!(
+            AdapterChain::default().plan(&[1, 2, 6, 7]),
+            Err(ChainError::Unbridgeable {
+                position: 3,
+                from: 2,
+                to: 6,
+                gap: 4
+            })
+        );
+        assert_eq!(
+            AdapterChain::new(vec![1, 2, 5]).plan(&[2, 3]),
+            Err(ChainError::Unbridgeable {
+                position: 3,
+                from: 3,
+                to: 6,
+                gap: 3
+            })
+        );
+        assert_eq!(
+            AdapterChain::default().plan(&[4]),
+            Err(ChainError::Unbridgeable {
+                position: 1,
+                from: 0,
+                to: 4,
+                gap: 4
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_device_past_largest_joltage() {
+        assert_eq!(
+            AdapterChain::default().plan(&[u32::MAX - 1, u32::MAX]),
+            Err(ChainError::DeviceOverflow {
+                highest: u32::MAX,
+                gap: 3
+            })
+        );
+        assert_eq!(
+            AdapterChain::default().device_gap(u32::MAX).plan(&[1]),
+            Err(ChainError::DeviceOverflow {
+                highest: 1,
+                gap: u32::MAX
+            })
+        );
+        assert_eq!(
+            AdapterChain::new(vec![1, 2, 3])
+                .plan(&[u32::MAX - 3])
+                .unwrap_err(),
+            ChainError::Unbridgeable {
+                position: 1,
+                from: 0,
+                to: u32::MAX - 3,
+                gap: u32::MAX - 3
+            }
+        );
+    }
+}
@@ -0,0 +1,144 @@
+mod arrange;
+mod chain;
+
+pub use arrange::{Arrangements, Constraints, Iter, Sampler};
+pub use chain::{AdapterChain, Chain, ChainError};
+use std::collections::HashSet;
+use std::io::BufRead;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum AocError {
+    #[error("Error reading input")]
+    Io(#[from] std::io::Error),
+    #[error("Parse int error")]
+    ParseErr(#[from] std::num::ParseIntError),
+    #[error("No adapters in the bag")]
+    Empty,
+    #[error("Adapter {0} appears more than once")]
+    Duplicate(u32),
+    #[error(transparent)]
+    Chain(#[from] ChainError),
+}
+
+/// Every adapter in the bag, sorted by joltage. The outlet at 0 jolts and the device 3 jolts
+/// above the highest adapter are implied, they are never part of the bag itself.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AdapterBag {
+    adapters: Vec<u32>,
+}
+
+impl AdapterBag {
+    /// Rejects an empty bag, or one where two adapters share a joltage
+    pub fn new(mut adapters: Vec<u32>) -> Result<Self, AocError> {
+        if adapters.is_empty() {
+            return Err(AocError::Empty);
+        }
+
+        let mut seen = HashSet::with_capacity(adapters.len());
+        if let Some(&duplicate) = adapters.iter().find(|&&joltage| !seen.insert(joltage)) {
+            return Err(AocError::Duplicate(duplicate));
+        }
+
+        adapters.sort_unstable();
+
+        Ok(Self { adapters })
+    }
+
+    /// Reads one joltage per line, blank lines are skipped
+    pub fn from_reader<R: BufRead>(reader: R) -> Result<Self, AocError> {
+        let adapters = reader
+            .lines()
+            .filter(|line| !matches!(line, Ok(line) if line.trim().is_empty()))
+            .map(|line| Ok(line?.trim().parse()?))
+            .collect::<Result<_, AocError>>()?;
+
+        Self::new(adapters)
+    }
+
+    pub fn adapters(&self) -> &[u32] {
+        &self.adapters
+    }
+
+    /// The device's rating, 3 jolts above the highest adapter. Fails if that doesn't fit in a
+    /// `u32`.
+    pub fn device(&self) -> Result<u32, AocError> {
+        let highest = self.adapters[self.adapters.len() - 1];
+
+        Ok(highest
+            .checked_add(3)
+            .ok_or(ChainError::DeviceOverflow { highest, gap: 3 })?)
+    }
+
+    /// The chain through every adapter from the outlet to the device, with the puzzle's 1 to 3
+    /// jolt tolerance
+    pub fn jolt_differences(&self) -> Result<Chain, AocError> {
+        Ok(AdapterChain::default().plan(&self.adapters)?)
+    }
+
+    /// Every arrangement of the adapters with the puzzle's 1 to 3 jolt tolerance
+    pub fn arrangements(&self) -> Result<Arrangements, AocError> {
+        Ok(Arrangements::new(&AdapterChain::default(), &self.adapters)?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use num_bigint::BigUint;
+    use std::io::Cursor;
+
+    #[test]
+    fn part_1() {
+        let input = "16\n10\n15\n5\n1\n11\n7\n19\n6\n12\n4\n";
+        let bag = AdapterBag::from_reader(Cursor::new(input)).unwrap();
+        let chain = bag.jolt_differences().unwrap();
+
+        assert_eq!(chain.count(1) * chain.count(3), 35);
+        assert_eq!(bag.device().unwrap(), 22);
+        assert_eq!(bag.arrangements().unwrap().count(), &BigUint::from(8u32));
+    }
+
+    #[test]
+    fn rejects_bad_bags() {
+        assert!(matches!(
+            AdapterBag::from_reader(Cursor::new("\n")),
+            Err(AocError::Empty)
+        ));
+        assert!(matches!(
+            AdapterBag::from_reader(Cursor::new("3\n1\n3\n")),
+            Err(AocError::Duplicate(3))
+        ));
+        assert!(matches!(
+            AdapterBag::from_reader(Cursor::new("3\nthree\n")),
+            Err(AocError::ParseErr(_))
+        ));
+        assert!(matches!(
+            AdapterBag::new(vec![1, 5]).unwrap().jolt_differences(),
+            Err(AocError::Chain(ChainError::Unbridgeable {
+                position: 2,
+                ..
+            }))
+        ));
+
+        let bag = AdapterBag::new(vec![u32::MAX - 4, u32::MAX - 2]).unwrap();
+        assert!(matches!(
+            bag.device(),
+            Err(AocError::Chain(ChainError::DeviceOverflow {
+                highest: 4294967293,
+                gap: 3
+            }))
+        ));
+        assert!(matches!(
+            bag.jolt_differences(),
+            Err(AocError::Chain(ChainError::DeviceOverflow { .. }))
+        ));
+        assert_eq!(
+            AdapterBag::new(vec![u32::MAX - 3])
+                .unwrap()
+                .device()
+                .unwrap(),
+            u32::MAX
+        );
+    }
+}
@@ -0,0 +1,130 @@
+use aoc11::{Checkpoint, GifRecorder, Outcome, Palette, Rules, Seating};
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+
+const USAGE: &str = "Usage: animate [--los] [--gif <file> | --frames <dir>] [--scale <n>] \
+                     [--highlight] [--limit <n>] [--lattice <square | hex | cubic>] \
+                     [--boundary <bounded | toroidal | reflective | unbounded>] \
+                     [--checkpoint <file>] [--resume <file> | < seating]";
+
+enum Output {
+    Text,
+    Gif(PathBuf),
+    Frames(PathBuf),
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut rules = Rules::adjacent();
+    let mut output = Output::Text;
+    let mut palette = Palette::default();
+    let mut scale = 4;
+    let mut limit = Some(1000);
+    let mut lattice = None;
+    let mut boundary = None;
+    let mut save = None;
+    let mut resume = None;
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or(USAGE);
+
+        match arg.as_str() {
+            "--los" => rules = Rules::line_of_sight(),
+            "--gif" => output = Output::Gif(value()?.into()),
+            "--frames" => output = Output::Frames(value()?.into()),
+            "--scale" => scale = value()?.parse()?,
+            "--highlight" => palette.changed = Some([0xff, 0xd7, 0x00]),
+            "--limit" => limit = Some(value()?.parse()?),
+            "--lattice" => lattice = Some(value()?.parse().map_err(|_| USAGE)?),
+            "--boundary" => boundary = Some(value()?.parse().map_err(|_| USAGE)?),
+            "--checkpoint" => save = Some(PathBuf::from(value()?)),
+            "--resume" => resume = Some(PathBuf::from(value()?)),
+            _ => return Err(USAGE.into()),
+        }
+    }
+
+    let mut checkpoint = match resume {
+        Some(path) => Checkpoint::load(path)?,
+        None => Checkpoint::new(0, Seating::from_reader(io::stdin().lock())?),
+    };
+    if let Some(lattice) = lattice {
+        checkpoint.seating.set_lattice(lattice);
+    }
+    if let Some(boundary) = boundary {
+        checkpoint.seating.set_boundary(boundary);
+    }
+
+    // the observer can't return errors, so the first one is kept and the rest of the frames are
+    // skipped
+    let mut error: Option<Box<dyn std::error::Error>> = None;
+    let outcome = match output {
+        Output::Text => {
+            let stdout = io::stdout();
+            let mut out = stdout.lock();
+
+            checkpoint.resume_with(&rules, limit, |generation, seating| {
+                if error.is_none() {
+                    let frame = writeln!(out, "Generation {}", generation)
+                        .and_then(|_| seating.write_text(&mut out))
+                        .and_then(|_| writeln!(out));
+                    error = frame.err().map(Into::into);
+                }
+            })
+        }
+        Output::Gif(path) => {
+            let file = BufWriter::new(File::create(path)?);
+            let mut recorder = GifRecorder::new(file, palette, scale);
+
+            let outcome = checkpoint.resume_with(&rules, limit, |_, seating| {
+                if error.is_none() {
+                    error = recorder.frame(seating).err().map(Into::into);
+                }
+            });
+            recorder.finish()?.flush()?;
+            outcome
+        }
+        Output::Frames(dir) => {
+            fs::create_dir_all(&dir)?;
+
+            checkpoint.resume_with(&rules, limit, |generation, seating| {
+                if error.is_none() {
+                    let path = dir.join(format!("frame_{:05}.ppm", generation));
+                    let frame = File::create(path).and_then(|file| {
+                        let mut writer = BufWriter::new(file);
+                        seating.write_ppm(&mut writer, &palette, scale)?;
+                        writer.flush()
+                    });
+                    error = frame.err().map(Into::into);
+                }
+            })
+        }
+    };
+
+    if let Some(error) = error {
+        return Err(error);
+    }
+
+    let taken = checkpoint.seating.num_seats();
+    match outcome {
+        Outcome::Stable { generations } => println!(
+            "Stable after {} generations, {} seats taken",
+            generations, taken
+        ),
+        Outcome::Cycle { start, period } => println!(
+            "Cycle of {} generations from generation {}, {} seats taken",
+            period, start, taken
+        ),
+        Outcome::LimitReached { generations } => println!(
+            "Still changing after {} generations, {} seats taken",
+            generations, taken
+        ),
+    }
+
+    if let Some(path) = save {
+        checkpoint.save(path)?;
+    }
+
+    Ok(())
+}
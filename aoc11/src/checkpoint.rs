@@ -0,0 +1,305 @@
+//! Text format for saving a simulation part way through.
+//!
+//! ```text
+//! aoc11 checkpoint
+//! generation 12
+//! lattice square
+//! boundary toroidal
+//! origin 2 2 0
+//!
+//! L.LL.LL.LL
+//! ...
+//! ```
+//!
+//! The seating is written the same way as the puzzle input, after a blank line. Layers after the
+//! first are separated by blank lines too.
+use crate::{AocError, Outcome, Rules, Seating};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+
+const HEADER: &str = "aoc11 checkpoint";
+
+/// A seating along with how many generations it has been run for
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Checkpoint {
+    pub generation: usize,
+    pub seating: Seating,
+}
+
+impl Checkpoint {
+    pub fn new(generation: usize, seating: Seating) -> Self {
+        Self {
+            generation,
+            seating,
+        }
+    }
+
+    /// Writes the checkpoint. The lattice, boundary and origin are kept, the number of threads
+    /// isn't.
+    pub fn write<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writeln!(writer, "{}", HEADER)?;
+        writeln!(writer, "generation {}", self.generation)?;
+        writeln!(writer, "lattice {}", self.seating.lattice)?;
+        writeln!(writer, "boundary {}", self.seating.boundary)?;
+        let (x, y, z) = self.seating.origin;
+        writeln!(writer, "origin {} {} {}", x, y, z)?;
+        writeln!(writer)?;
+
+        self.seating.write_text(writer)
+    }
+
+    pub fn read<R: BufRead>(mut reader: R) -> Result<Self, AocError> {
+        let mut header = String::new();
+        reader.read_line(&mut header)?;
+        if header.trim_end() != HEADER {
+            return Err(AocError::BadCheckpoint("header"));
+        }
+
+        let mut field = |name: &'static str| -> Result<Vec<String>, AocError> {
+            let mut line = String::new();
+            reader.read_line(&mut line)?;
+
+            let mut words = line.split_whitespace();
+            if words.next() != Some(name) {
+                return Err(AocError::BadCheckpoint(name));
+            }
+
+            Ok(words.map(String::from).collect())
+        };
+
+        let number = |word: Option<&String>, name| {
+            word.and_then(|word| word.parse::<usize>().ok())
+                .ok_or(AocError::BadCheckpoint(name))
+        };
+
+        let generation = number(field("generation")?.first(), "generation")?;
+        let lattice = field("lattice")?
+            .first()
+            .and_then(|word| word.parse().ok())
+            .ok_or(AocError::BadCheckpoint("lattice"))?;
+        let boundary = field("boundary")?
+            .first()
+            .and_then(|word| word.parse().ok())
+            .ok_or(AocError::BadCheckpoint("boundary"))?;
+        let origin = field("origin")?;
+        let origin = (
+            number(origin.first(), "origin")?,
+            number(origin.get(1), "origin")?,
+            number(origin.get(2), "origin")?,
+        );
+
+        let mut blank = String::new();
+        reader.read_line(&mut blank)?;
+        if !blank.trim().is_empty() {
+            return Err(AocError::BadCheckpoint("seating"));
+        }
+
+        let mut seating = Seating::from_reader(reader)?;
+        if origin.0 > seating.width || origin.1 > seating.height || origin.2 > seating.depth {
+            return Err(AocError::BadCheckpoint("origin"));
+        }
+        seating.lattice = lattice;
+        seating.boundary = boundary;
+        seating.origin = origin;
+
+        Ok(Self::new(generation, seating))
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), AocError> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write(&mut writer)?;
+        writer.flush()?;
+
+        Ok(())
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, AocError> {
+        Self::read(BufReader::new(File::open(path)?))
+    }
+
+    /// Carries on running the seating, see `Seating::evolve`. `limit` and the generations in the
+    /// outcome count from the start of the original run rather than from the checkpoint, and the
+    /// checkpoint is left at the last generation run. A cycle can only be found as starting from
+    /// the checkpoint onwards, as nothing earlier is kept.
+    pub fn resume(&mut self, rules: &Rules, limit: Option<usize>) -> Outcome {
+        self.resume_with(rules, limit, |_, _| {})
+    }
+
+    /// Same as `resume`, calling `observer` with the checkpointed seating and then after every
+    /// generation that changes something
+    pub fn resume_with<F>(
+        &mut self,
+        rules: &Rules,
+        limit: Option<usize>,
+        mut observer: F,
+    ) -> Outcome
+    where
+        F: FnMut(usize, &Seating),
+    {
+        let first = self.generation;
+        let mut last = first;
+
+        let limit = limit.map(|limit| limit.saturating_sub(first));
+        let outcome = self
+            .seating
+            .evolve_with(rules, limit, |generation, seating| {
+                last = first + generation;
+                observer(last, seating);
+            });
+        self.generation = last;
+
+        match outcome {
+            Outcome::Stable { generations } => Outcome::Stable {
+                generations: first + generations,
+            },
+            Outcome::Cycle { start, period } => Outcome::Cycle {
+                start: first + start,
+                period,
+            },
+            Outcome::LimitReached { generations } => Outcome::LimitReached {
+                generations: first + generations,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Boundary, Lattice, Neighbourhood, Space};
+
+    const EXAMPLE: &str = "\
+L.LL.LL.LL
+LLLLLLL.LL
+L.L.L..L..
+LLLL.LL.LL
+L.LL.LL.LL
+L.LLLLL.LL
+..L.L.....
+LLLLLLLLLL
+L.LLLLLL.L
+L.LLLLL.LL
+";
+
+    #[test]
+    fn display_round_trips() {
+        let seating: Seating = EXAMPLE.parse().unwrap();
+
+        assert_eq!(seating.to_string(), EXAMPLE);
+        assert_eq!(seating.to_string().parse::<Seating>().unwrap(), seating);
+        assert_eq!("".parse::<Seating>().unwrap().to_string(), "");
+    }
+
+    #[test]
+    fn matches_golden_generations() {
+        let mut frames = Vec::new();
+        EXAMPLE
+            .parse::<Seating>()
+            .unwrap()
+            .evolve_with(&Rules::adjacent(), None, |_, seating| {
+                frames.push(seating.to_string())
+            });
+
+        assert_eq!(frames[1], EXAMPLE.replace('L', "#"));
+        assert_eq!(
+            frames[2],
+            "\
+#.LL.L#.##
+#LLLLLL.L#
+L.L.L..L..
+#LLL.LL.L#
+#.LL.LL.LL
+#.LLLL#.##
+..L.L.....
+#LLLLLLLL#
+#.LLLLLL.L
+#.#LLLL.##
+"
+        );
+    }
+
+    #[test]
+    fn saves_and_loads() {
+        let mut seating: Seating = "#.\n.L".parse().unwrap();
+        seating.set_lattice(Lattice::Hex);
+        seating.set_boundary(Boundary::Unbounded);
+        seating.step_with(&Rules {
+            neighbourhood: Neighbourhood::Moore(2),
+            ..Rules::adjacent()
+        });
+
+        let checkpoint = Checkpoint::new(1, seating);
+        let mut bytes = Vec::new();
+        checkpoint.write(&mut bytes).unwrap();
+        let text = String::from_utf8(bytes).unwrap();
+        assert!(text.starts_with(
+            "aoc11 checkpoint\ngeneration 1\nlattice hex\nboundary unbounded\norigin 2 2 0\n\n"
+        ));
+
+        let loaded = Checkpoint::read(text.as_bytes()).unwrap();
+        assert_eq!(loaded, checkpoint);
+        assert_eq!(loaded.seating.boundary(), Boundary::Unbounded);
+        assert_eq!(loaded.seating.lattice(), Lattice::Hex);
+        assert_eq!(loaded.seating.origin(), (2, 2, 0));
+        assert_eq!(loaded.seating.get(2, 2), Some(Space::Taken));
+
+        let path = std::env::temp_dir().join(format!("aoc11_checkpoint_{}", std::process::id()));
+        checkpoint.save(&path).unwrap();
+        assert_eq!(Checkpoint::load(&path).unwrap(), checkpoint);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn rejects_malformed_checkpoints() {
+        let bad = |text: &str| match Checkpoint::read(text.as_bytes()) {
+            Err(AocError::BadCheckpoint(field)) => field,
+            other => panic!("expected a bad checkpoint, got {:?}", other),
+        };
+
+        assert_eq!(bad("L.L\n"), "header");
+        assert_eq!(bad("aoc11 checkpoint\ngeneration x\n"), "generation");
+        assert_eq!(
+            bad("aoc11 checkpoint\ngeneration 1\nlattice triangle\n"),
+            "lattice"
+        );
+        assert_eq!(
+            bad("aoc11 checkpoint\ngeneration 1\nlattice square\nboundary round\n"),
+            "boundary"
+        );
+        assert_eq!(
+            bad("aoc11 checkpoint\ngeneration 1\nlattice square\nboundary bounded\norigin 0 0 0\nL\n"),
+            "seating"
+        );
+        assert_eq!(
+            bad("aoc11 checkpoint\ngeneration 1\nlattice square\nboundary bounded\norigin 0 0 2\n\nL\n"),
+            "origin"
+        );
+    }
+
+    #[test]
+    fn resumes_where_it_left_off() {
+        let rules = Rules::adjacent();
+        let mut full: Seating = EXAMPLE.parse().unwrap();
+        let expected = full.evolve(&rules, None);
+
+        let mut partial: Seating = EXAMPLE.parse().unwrap();
+        assert_eq!(
+            partial.evolve(&rules, Some(2)),
+            Outcome::LimitReached { generations: 2 }
+        );
+
+        let mut bytes = Vec::new();
+        Checkpoint::new(2, partial).write(&mut bytes).unwrap();
+        let mut checkpoint = Checkpoint::read(bytes.as_slice()).unwrap();
+
+        let mut observed = Vec::new();
+        let outcome =
+            checkpoint.resume_with(&rules, None, |generation, _| observed.push(generation));
+
+        assert_eq!(outcome, expected);
+        assert_eq!(observed, vec![2, 3, 4, 5]);
+        assert_eq!(checkpoint.generation, 5);
+        assert_eq!(checkpoint.seating, full);
+    }
+}
@@ -0,0 +1,190 @@
+use crate::{AocError, Seating, Space};
+use std::fmt;
+
+/// A cell that differs between two seatings
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Change {
+    pub x: usize,
+    pub y: usize,
+    pub z: usize,
+    pub from: Space,
+    pub to: Space,
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "({}, {}, {}) {} -> {}",
+            self.x,
+            self.y,
+            self.z,
+            self.from.to_byte() as char,
+            self.to.to_byte() as char
+        )
+    }
+}
+
+/// How many seats there are in a row, column or layer, and how many of them are taken
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Occupancy {
+    pub seats: usize,
+    pub taken: usize,
+}
+
+impl Occupancy {
+    /// Fraction of the seats that are taken, 0 when there aren't any seats
+    pub fn ratio(&self) -> f64 {
+        if self.seats == 0 {
+            return 0.0;
+        }
+
+        self.taken as f64 / self.seats as f64
+    }
+
+    fn add(&mut self, space: Space) {
+        match space {
+            Space::Floor => {}
+            Space::Empty => self.seats += 1,
+            Space::Taken => {
+                self.seats += 1;
+                self.taken += 1;
+            }
+        }
+    }
+}
+
+impl Seating {
+    /// Every cell that is different in `other`, in reading order. Both seatings have to be the
+    /// same size.
+    pub fn diff(&self, other: &Seating) -> Result<Vec<Change>, AocError> {
+        let expected = (self.width, self.height, self.depth);
+        let found = (other.width, other.height, other.depth);
+        if expected != found {
+            return Err(AocError::SizeMismatch { expected, found });
+        }
+
+        Ok(self
+            .cells
+            .iter()
+            .zip(other.cells.iter())
+            .enumerate()
+            .filter(|(_, (from, to))| from != to)
+            .map(|(index, (&from, &to))| Change {
+                x: index % self.width,
+                y: index / self.width % self.height,
+                z: index / (self.width * self.height),
+                from,
+                to,
+            })
+            .collect())
+    }
+
+    /// Occupancy of each row, top to bottom, counting the row in every layer
+    pub fn row_occupancy(&self) -> Vec<Occupancy> {
+        let mut rows = vec![Occupancy::default(); self.height];
+        for (index, &space) in self.cells.iter().enumerate() {
+            rows[index / self.width % self.height].add(space);
+        }
+
+        rows
+    }
+
+    /// Occupancy of each layer, first to last
+    pub fn layer_occupancy(&self) -> Vec<Occupancy> {
+        let mut layers = vec![Occupancy::default(); self.depth];
+        for (index, &space) in self.cells.iter().enumerate() {
+            layers[index / (self.width * self.height)].add(space);
+        }
+
+        layers
+    }
+
+    /// Occupancy of each column, left to right, counting the column in every layer
+    pub fn column_occupancy(&self) -> Vec<Occupancy> {
+        let mut columns = vec![Occupancy::default(); self.width];
+        for (index, &space) in self.cells.iter().enumerate() {
+            columns[index % self.width].add(space);
+        }
+
+        columns
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Rules;
+
+    #[test]
+    fn lists_changes() {
+        let before: Seating = "L.L\nL.#".parse().unwrap();
+        let mut after = before.clone();
+        after.step_with(&Rules::adjacent());
+
+        let changes = before.diff(&after).unwrap();
+        assert_eq!(
+            changes,
+            vec![
+                Change {
+                    x: 0,
+                    y: 0,
+                    z: 0,
+                    from: Space::Empty,
+                    to: Space::Taken
+                },
+                Change {
+                    x: 0,
+                    y: 1,
+                    z: 0,
+                    from: Space::Empty,
+                    to: Space::Taken
+                },
+            ]
+        );
+        assert_eq!(changes[1].to_string(), "(0, 1, 0) L -> #");
+        assert!(after.diff(&after).unwrap().is_empty());
+
+        assert!(matches!(
+            before.diff(&"L".parse().unwrap()),
+            Err(AocError::SizeMismatch {
+                expected: (3, 2, 1),
+                found: (1, 1, 1)
+            })
+        ));
+    }
+
+    #[test]
+    fn counts_occupancy() {
+        let seating: Seating = "#.L\nL##\n...".parse().unwrap();
+
+        let rows = seating.row_occupancy();
+        assert_eq!(rows[0], Occupancy { seats: 2, taken: 1 });
+        assert_eq!(rows[1], Occupancy { seats: 3, taken: 2 });
+        assert_eq!(rows[2].ratio(), 0.0);
+
+        let columns = seating.column_occupancy();
+        assert_eq!(
+            columns
+                .iter()
+                .map(|column| column.taken)
+                .collect::<Vec<_>>(),
+            vec![1, 1, 1]
+        );
+        assert_eq!(columns[1].ratio(), 1.0);
+
+        let tiers: Seating = "#L\nLL\n\n##\n.L".parse().unwrap();
+        assert_eq!(
+            tiers.layer_occupancy(),
+            vec![
+                Occupancy { seats: 4, taken: 1 },
+                Occupancy { seats: 3, taken: 2 }
+            ]
+        );
+        assert_eq!(tiers.row_occupancy()[0], Occupancy { seats: 4, taken: 3 });
+        assert_eq!(
+            tiers.diff(&seating).unwrap_err().to_string(),
+            "Seating is 3x3x1 cells but expected 2x2x2"
+        );
+    }
+}
@@ -0,0 +1,183 @@
+use crate::{Rules, Seating};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+
+/// How a seating ended up after running it with `Seating::evolve`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Outcome {
+    /// Stopped changing after this many generations
+    Stable { generations: usize },
+    /// Generation `start + period` is the same as generation `start`, and it repeats from there
+    /// forever. Generation 0 is the starting seating.
+    Cycle { start: usize, period: usize },
+    /// Still changing when the generation limit was reached
+    LimitReached { generations: usize },
+}
+
+impl Seating {
+    /// Runs generations until the seating stops changing or returns to an earlier state, or
+    /// until `limit` generations have run.
+    ///
+    /// Only a hash of each generation is kept. When a hash comes up again the earlier generation
+    /// is rebuilt from the start and compared in full, so a hash collision can't be mistaken for
+    /// a cycle.
+    pub fn evolve(&mut self, rules: &Rules, limit: Option<usize>) -> Outcome {
+        self.evolve_with(rules, limit, |_, _| {})
+    }
+
+    /// Same as `evolve`, calling `observer` with the starting seating as generation 0 and then
+    /// after every generation that changes something
+    pub fn evolve_with<F>(
+        &mut self,
+        rules: &Rules,
+        limit: Option<usize>,
+        mut observer: F,
+    ) -> Outcome
+    where
+        F: FnMut(usize, &Seating),
+    {
+        // an unbounded seating grows before the first generation is observed
+        self.prepare(&rules.neighbourhood);
+        observer(0, self);
+
+        let initial = self.clone();
+        let mut seen: HashMap<u64, Vec<usize>> = HashMap::new();
+        seen.entry(self.fingerprint()).or_default().push(0);
+
+        let mut generation = 0;
+        loop {
+            if limit.is_some_and(|limit| generation >= limit) {
+                return Outcome::LimitReached {
+                    generations: generation,
+                };
+            }
+
+            if !self.step_with(rules) {
+                return Outcome::Stable {
+                    generations: generation,
+                };
+            }
+            generation += 1;
+            observer(generation, self);
+
+            let earlier = seen.entry(self.fingerprint()).or_default();
+            for &start in earlier.iter() {
+                let mut replay = initial.clone();
+                for _ in 0..start {
+                    replay.step_with(rules);
+                }
+
+                if replay == *self {
+                    return Outcome::Cycle {
+                        start,
+                        period: generation - start,
+                    };
+                }
+            }
+            earlier.push(generation);
+        }
+    }
+
+    fn fingerprint(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.hash(&mut hasher);
+
+        hasher.finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Neighbourhood;
+    use std::io::Cursor;
+
+    fn seating(input: &str) -> Seating {
+        Seating::from_reader(Cursor::new(input)).unwrap()
+    }
+
+    #[test]
+    fn stabilises() {
+        let mut seating = seating("L.LL\nLLLL\nL.L.");
+
+        assert_eq!(
+            seating.evolve(&Rules::adjacent(), None),
+            Outcome::Stable { generations: 2 }
+        );
+    }
+
+    #[test]
+    fn detects_cycles() {
+        // seats flip every generation whatever their neighbours are doing
+        let blink = Rules {
+            neighbourhood: Neighbourhood::Moore(1),
+            birth: 0..9,
+            survival: 0..0,
+        };
+
+        assert_eq!(
+            seating("L.L\n.#.").evolve(&blink, None),
+            Outcome::Cycle {
+                start: 0,
+                period: 2
+            }
+        );
+
+        // only fills up beside a taken seat and only empties when crowded, so the first few
+        // generations are a lead in before the ends start flipping
+        let rules = Rules {
+            neighbourhood: Neighbourhood::Moore(1),
+            birth: 1..9,
+            survival: 0..2,
+        };
+        let mut row = seating("#LLLL");
+        match row.evolve(&rules, None) {
+            Outcome::Cycle { start, period } => {
+                let mut replay = seating("#LLLL");
+                for _ in 0..start {
+                    replay.step_with(&rules);
+                }
+                let first = replay.clone();
+                for _ in 0..period {
+                    replay.step_with(&rules);
+                }
+
+                assert_eq!(replay, first);
+                assert!(start > 0);
+            }
+            outcome => panic!("expected a cycle, got {:?}", outcome),
+        }
+    }
+
+    #[test]
+    fn observes_every_generation() {
+        let mut counts = Vec::new();
+        let outcome = seating("L.LL\nLLLL\nL.L.").evolve_with(
+            &Rules::adjacent(),
+            None,
+            |generation, seating| counts.push((generation, seating.num_seats())),
+        );
+
+        assert_eq!(outcome, Outcome::Stable { generations: 2 });
+        assert_eq!(counts, vec![(0, 0), (1, 9), (2, 5)]);
+    }
+
+    #[test]
+    fn stops_at_limit() {
+        let blink = Rules {
+            neighbourhood: Neighbourhood::Moore(1),
+            birth: 0..9,
+            survival: 0..0,
+        };
+
+        assert_eq!(
+            seating("L").evolve(&blink, Some(1)),
+            Outcome::LimitReached { generations: 1 }
+        );
+        assert_eq!(
+            seating("L.LL\nLLLL\nL.L.").evolve(&Rules::adjacent(), Some(0)),
+            Outcome::LimitReached { generations: 0 }
+        );
+    }
+}
@@ -1,32 +1,50 @@
+mod checkpoint;
+mod diff;
+mod evolve;
+mod neighbours;
+mod render;
+mod rules;
+
+pub use checkpoint::Checkpoint;
+pub use diff::{Change, Occupancy};
+pub use evolve::Outcome;
+use neighbours::Neighbours;
+pub use render::{GifRecorder, Palette};
+pub use rules::{Boundary, Lattice, Neighbourhood, Rules};
 use std::convert::{TryFrom, TryInto};
-use std::default::Default;
+use std::hash::{Hash, Hasher};
 use std::io::BufRead;
-use std::iter;
+use std::mem;
+use std::str::FromStr;
+use std::thread;
 use thiserror::Error;
 
-/// Order of directions is important for line of sight in part_2
-static DIRECTIONS: [(i32, i32); 8] = [
-    (-1, -1),
-    (0, -1),
-    (1, -1),
-    (-1, 0),
-    (1, 0),
-    (-1, 1),
-    (0, 1),
-    (1, 1),
-];
-
 #[derive(Error, Debug)]
 pub enum AocError {
     #[error("Unrecognized input error")]
     ParseInputError,
     #[error("Error reading input")]
     IoError(#[from] std::io::Error),
+    #[error("Image of {width}x{height} pixels is too large for a GIF")]
+    ImageTooLarge { width: usize, height: usize },
+    #[error(
+        "Seating is {}x{}x{} cells but expected {}x{}x{}",
+        found.0, found.1, found.2, expected.0, expected.1, expected.2
+    )]
+    SizeMismatch {
+        expected: (usize, usize, usize),
+        found: (usize, usize, usize),
+    },
+    #[error("Error encoding GIF")]
+    GifError(#[from] gif::EncodingError),
+    #[error("Malformed checkpoint: {0}")]
+    BadCheckpoint(&'static str),
 }
 
-#[derive(Copy, Clone, Debug, PartialEq)]
-enum Space {
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Space {
     Empty,
+    #[default]
     Floor,
     Taken,
 }
@@ -44,239 +62,339 @@ impl TryFrom<u8> for Space {
     }
 }
 
-impl Default for Space {
-    fn default() -> Self {
-        Self::Floor
+/// The seating area as a flat row-major buffer, one layer after another. Each generation is
+/// written into a second buffer and the two are swapped, so stepping doesn't allocate once the
+/// neighbour table for the rules has been built.
+#[derive(Clone, Debug)]
+pub struct Seating {
+    width: usize,
+    height: usize,
+    depth: usize,
+    cells: Vec<Space>,
+    back: Vec<Space>,
+    /// Built the first time a neighbourhood is used and kept until a different one is
+    neighbours: Option<Neighbours>,
+    threads: usize,
+    lattice: Lattice,
+    boundary: Boundary,
+    /// Where the top left corner of the original seating is, once an unbounded seating has grown
+    origin: (usize, usize, usize),
+}
+
+impl PartialEq for Seating {
+    fn eq(&self, other: &Self) -> bool {
+        self.width == other.width
+            && self.height == other.height
+            && self.depth == other.depth
+            && self.cells == other.cells
     }
 }
 
-/// positions includes a layer of padding (Floor) around the seating area. Width and height also
-/// include the padding
-#[derive(Clone)]
-pub struct Seating {
-    positions: Vec<Vec<Space>>,
+impl Eq for Seating {}
+
+impl Hash for Seating {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.width.hash(state);
+        self.height.hash(state);
+        self.depth.hash(state);
+        self.cells.hash(state);
+    }
 }
 
 impl Seating {
+    /// Reads rows in the puzzle input format. Blank lines separate layers, which all have to be
+    /// the same size.
     pub fn from_reader<R: BufRead>(reader: R) -> Result<Self, AocError> {
-        let mut positions: Vec<Vec<Space>> = reader
-            .lines()
-            .map(|line| match line {
-                Ok(line) => iter::once(b'.')
-                    .chain(line.trim().bytes())
-                    .chain(iter::once(b'.'))
-                    .map(|b| b.try_into())
-                    .collect::<Result<_, _>>(),
-                Err(e) => Err(AocError::from(e)),
-            })
-            .collect::<Result<_, _>>()?;
-
-        let width = positions[0].len();
-        let padding = vec![Space::Floor; width];
-        positions.push(padding.clone());
-        positions.push(padding);
-        positions.rotate_right(1);
-
-        Ok(Self { positions })
-    }
-
-    /// Updates the seating according to how many taken seats are around each position, if the
-    /// seating changes then it returns true, if it's the same then it returns false
-    fn step(&mut self, counts: &Vec<Vec<u32>>, taken: u32) -> bool {
-        let mut changed = false;
-
-        counts.iter().enumerate().for_each(|(j, row)| {
-            row.iter().enumerate().for_each(|(i, &count)| {
-                let space = self
-                    .positions
-                    .get_mut(j + 1)
-                    .unwrap()
-                    .get_mut(i + 1)
-                    .unwrap();
-
-                match space {
-                    Space::Empty if count == 0 => {
-                        *space = Space::Taken;
-                        changed = true;
-                    }
-                    Space::Taken if count >= taken => {
-                        *space = Space::Empty;
-                        changed = true;
-                    }
-                    _ => {}
+        let mut layers: Vec<Vec<Vec<Space>>> = vec![Vec::new()];
+        for line in reader.lines() {
+            let line = line?;
+            let line = line.trim();
+
+            if line.is_empty() {
+                if !layers.last().unwrap().is_empty() {
+                    layers.push(Vec::new());
                 }
-            });
-        });
+                continue;
+            }
 
-        changed
+            let row = line
+                .bytes()
+                .map(|b| b.try_into())
+                .collect::<Result<_, _>>()?;
+            layers.last_mut().unwrap().push(row);
+        }
+        if layers.len() > 1 && layers.last().unwrap().is_empty() {
+            layers.pop();
+        }
+
+        let width = layers[0].first().map_or(0, |row| row.len());
+        let height = layers[0].len();
+        let ragged = layers
+            .iter()
+            .any(|layer| layer.len() != height || layer.iter().any(|row| row.len() != width));
+        if ragged {
+            return Err(AocError::ParseInputError);
+        }
+
+        Ok(Self::new(width, height, layers.concat().concat()))
     }
 
-    pub fn process_adjacent(&mut self) {
-        loop {
-            let counts = self.build_counts_adjacent();
-            if !self.step(&counts, 4) {
-                break;
-            }
+    fn new(width: usize, height: usize, cells: Vec<Space>) -> Self {
+        Self {
+            width,
+            height,
+            depth: cells.len().checked_div(width * height).unwrap_or(0),
+            back: cells.clone(),
+            cells,
+            neighbours: None,
+            threads: 1,
+            lattice: Lattice::Square,
+            boundary: Boundary::Bounded,
+            origin: (0, 0, 0),
         }
     }
 
-    pub fn process_los(&mut self) {
-        loop {
-            let counts = self.build_counts_los();
-            if !self.step(&counts, 5) {
-                break;
-            }
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Number of layers
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// The space at `(x, y)` in the first layer, `None` off the edge of the seating area
+    pub fn get(&self, x: usize, y: usize) -> Option<Space> {
+        self.get_voxel(x, y, 0)
+    }
+
+    /// The space at `(x, y)` in layer `z`, `None` off the edge of the seating area
+    pub fn get_voxel(&self, x: usize, y: usize, z: usize) -> Option<Space> {
+        if x >= self.width || y >= self.height || z >= self.depth {
+            return None;
         }
+
+        Some(self.cells[(z * self.height + y) * self.width + x])
     }
 
-    pub fn num_seats(&self) -> u32 {
-        self.positions.iter().flatten().fold(0, |acc, seat| {
-            if *seat == Space::Taken {
-                return acc + 1;
-            }
-            acc
-        })
+    /// Where the top left corner of the first layer of the original seating has ended up, only
+    /// ever moves when an unbounded seating grows
+    pub fn origin(&self) -> (usize, usize, usize) {
+        self.origin
     }
 
-    /// builds a structure that counts all of the adjacent taken seats for a given position
-    fn build_counts_adjacent(&self) -> Vec<Vec<u32>> {
-        let counts: Vec<Vec<u32>> = self.positions[1..self.positions.len() - 1]
-            .iter()
-            .enumerate()
-            .map(|(j, row)| {
-                row[1..row.len() - 1]
-                    .iter()
-                    .enumerate()
-                    .map(|(i, _)| {
-                        DIRECTIONS.iter().fold(0, |acc, direction| {
-                            // + 1 to x and y as we are starting iteration from offset of 1
-                            let x = (i as i32 + direction.0 + 1) as usize;
-                            let y = (j as i32 + direction.1 + 1) as usize;
-
-                            if self.positions[y][x] == Space::Taken {
-                                return acc + 1;
-                            }
-                            acc
-                        })
-                    })
-                    .collect()
-            })
-            .collect();
+    pub fn lattice(&self) -> Lattice {
+        self.lattice
+    }
 
-        counts
+    /// Square by default. Only changes which cells are next to each other, the layout of the
+    /// cells stays the same.
+    pub fn set_lattice(&mut self, lattice: Lattice) {
+        self.lattice = lattice;
     }
 
-    /// builds a structure that counts the taken seats that are in line of sight.
-    fn build_counts_los(&self) -> Vec<Vec<u32>> {
-        // Include the buffer from positions
-        let mut los =
-            vec![vec![FloorLos::default(); self.positions[0].len()]; self.positions.len()];
+    pub fn boundary(&self) -> Boundary {
+        self.boundary
+    }
 
-        // iterate forwards through the positions looking behind to build up lines of sight for w,
-        // nw, n, and ne positions
-        for (j, row) in self.positions[1..self.positions.len() - 1]
-            .iter()
-            .enumerate()
-        {
-            for (i, &space) in row[1..row.len() - 1].iter().enumerate() {
-                if space == Space::Floor {
-                    for direction in DIRECTIONS[0..4].iter() {
-                        let x = (i as i32 + direction.0 + 1) as usize;
-                        let y = (j as i32 + direction.1 + 1) as usize;
-
-                        match self.positions[y][x] {
-                            Space::Empty => {
-                                *los[j + 1][i + 1].get_mut(direction) = Space::Empty;
-                            }
-                            Space::Floor => {
-                                *los[j + 1][i + 1].get_mut(direction) = *los[y][x].get(direction);
-                            }
-                            Space::Taken => {
-                                *los[j + 1][i + 1].get_mut(direction) = Space::Taken;
-                            }
-                        }
-                    }
-                }
-            }
+    pub fn set_boundary(&mut self, boundary: Boundary) {
+        self.boundary = boundary;
+    }
+
+    /// Splits each generation between this many threads by rows, 1 by default
+    pub fn set_threads(&mut self, threads: usize) {
+        self.threads = threads.max(1);
+    }
+
+    /// Makes sure the neighbour table matches the neighbourhood, lattice and boundary, growing an
+    /// unbounded seating first if it needs to
+    fn prepare(&mut self, neighbourhood: &Neighbourhood) {
+        if self.boundary == Boundary::Unbounded {
+            self.grow(neighbourhood.reach(self.lattice));
         }
 
-        // iterate backwards through the positions looking behind to build up lines of sight for e,
-        // se, s, and sw positions
-        for (j, row) in self.positions[1..self.positions.len() - 1]
-            .iter()
-            .enumerate()
-            .rev()
-        {
-            for (i, &space) in row[1..row.len() - 1].iter().enumerate().rev() {
-                if space == Space::Floor {
-                    for direction in DIRECTIONS[4..].iter() {
-                        let x = (i as i32 + direction.0 + 1) as usize;
-                        let y = (j as i32 + direction.1 + 1) as usize;
-
-                        match self.positions[y][x] {
-                            Space::Empty => {
-                                *los[j + 1][i + 1].get_mut(direction) = Space::Empty;
-                            }
-                            Space::Floor => {
-                                *los[j + 1][i + 1].get_mut(direction) = *los[y][x].get(direction);
-                            }
-                            Space::Taken => {
-                                *los[j + 1][i + 1].get_mut(direction) = Space::Taken;
-                            }
-                        }
-                    }
-                }
+        let stale = self.neighbours.as_ref().is_none_or(|neighbours| {
+            neighbours.neighbourhood != *neighbourhood
+                || neighbours.lattice != self.lattice
+                || neighbours.boundary != self.boundary
+        });
+
+        if stale {
+            self.neighbours = Some(Neighbours::new(
+                neighbourhood,
+                self.lattice,
+                self.boundary,
+                (self.width, self.height, self.depth),
+                &self.cells,
+            ));
+        }
+    }
+
+    /// Pads the seating with floor until every seat has at least `margin` cells around it along
+    /// each axis. Floor never changes, so this only happens when the neighbourhood reaches further
+    /// than before.
+    fn grow(&mut self, margin: (usize, usize, usize)) {
+        let size = [self.width, self.height, self.depth];
+        let margin = [margin.0, margin.1, margin.2];
+        // floor to add before and after the seating along each axis
+        let mut before = [0; 3];
+        let mut after = [0; 3];
+
+        for (index, &space) in self.cells.iter().enumerate() {
+            if space == Space::Floor {
+                continue;
+            }
+
+            let at = [
+                index % self.width,
+                index / self.width % self.height,
+                index / (self.width * self.height),
+            ];
+            for axis in 0..3 {
+                before[axis] = before[axis].max(margin[axis].saturating_sub(at[axis]));
+                after[axis] =
+                    after[axis].max((at[axis] + margin[axis] + 1).saturating_sub(size[axis]));
             }
         }
 
-        // now what we have all of the lines of sight we can count the taken chairs around the
-        // given position
-        let counts: Vec<Vec<u32>> = self.positions[1..self.positions.len() - 1]
-            .iter()
-            .enumerate()
-            .map(|(j, row)| {
-                row[1..row.len() - 1]
-                    .iter()
+        if before.iter().chain(after.iter()).all(|&pad| pad == 0) {
+            return;
+        }
+
+        let [width, height, depth] = [0, 1, 2].map(|axis| before[axis] + size[axis] + after[axis]);
+        let mut cells = vec![Space::Floor; width * height * depth];
+        for (row, cells_in_row) in self.cells.chunks(self.width).enumerate() {
+            let (y, z) = (row % self.height, row / self.height);
+            let start = ((before[2] + z) * height + before[1] + y) * width + before[0];
+            cells[start..start + self.width].copy_from_slice(cells_in_row);
+        }
+
+        self.width = width;
+        self.height = height;
+        self.depth = depth;
+        self.back = cells.clone();
+        self.cells = cells;
+        self.neighbours = None;
+        self.origin = (
+            self.origin.0 + before[0],
+            self.origin.1 + before[1],
+            self.origin.2 + before[2],
+        );
+    }
+
+    /// Runs a single generation, returns whether anything changed
+    pub fn step_with(&mut self, rules: &Rules) -> bool {
+        self.prepare(&rules.neighbourhood);
+
+        let Seating {
+            width,
+            height,
+            depth,
+            cells,
+            back,
+            neighbours,
+            threads,
+            ..
+        } = self;
+        let neighbours = neighbours.as_ref().unwrap();
+        let cells = &*cells;
+
+        let changed = if *threads > 1 && *width > 0 {
+            let chunk_size = (*height * *depth).div_ceil(*threads) * *width;
+
+            thread::scope(|scope| {
+                let handles: Vec<_> = back
+                    .chunks_mut(chunk_size)
                     .enumerate()
-                    .map(|(i, _)| {
-                        DIRECTIONS.iter().fold(0, |acc, direction| {
-                            // + 1 to x and y as we are starting iteration from offset of 1
-                            let x = (i as i32 + direction.0 + 1) as usize;
-                            let y = (j as i32 + direction.1 + 1) as usize;
-
-                            match self.positions[y][x] {
-                                Space::Taken => acc + 1,
-                                Space::Floor if *los[y][x].get(direction) == Space::Taken => {
-                                    acc + 1
-                                }
-                                _ => acc,
-                            }
-                        })
+                    .map(|(chunk, out)| {
+                        let first = chunk * chunk_size;
+                        scope.spawn(move || next_generation(cells, neighbours, rules, first, out))
                     })
-                    .collect()
+                    .collect();
+
+                // join every handle, not just up to the first change
+                handles
+                    .into_iter()
+                    .fold(false, |changed, handle| handle.join().unwrap() | changed)
             })
-            .collect();
+        } else {
+            next_generation(cells, neighbours, rules, 0, back)
+        };
 
-        counts
+        mem::swap(&mut self.cells, &mut self.back);
+
+        changed
+    }
+
+    /// Runs generations until the seating stops changing, returns how many generations changed
+    /// something. Rules that never settle down will run forever, see `Seating::evolve`.
+    pub fn run(&mut self, rules: &Rules) -> usize {
+        let mut generations = 0;
+        while self.step_with(rules) {
+            generations += 1;
+        }
+
+        generations
     }
-}
 
-/// Wasted 9th element in the middle to make fetching the correct item easier
-#[derive(Default, Clone, Copy)]
-struct FloorLos([Space; 9]);
+    pub fn process_adjacent(&mut self) {
+        self.run(&Rules::adjacent());
+    }
 
-impl FloorLos {
-    fn get(&self, direction: &(i32, i32)) -> &Space {
-        let index = (direction.0 + 1 + (direction.1 + 1) * 3) as usize;
+    pub fn process_los(&mut self) {
+        self.run(&Rules::line_of_sight());
+    }
 
-        &self.0[index]
+    pub fn num_seats(&self) -> u32 {
+        self.cells
+            .iter()
+            .filter(|&&space| space == Space::Taken)
+            .count() as u32
     }
+}
+
+impl FromStr for Seating {
+    type Err = AocError;
 
-    fn get_mut(&mut self, direction: &(i32, i32)) -> &mut Space {
-        let index = (direction.0 + 1 + (direction.1 + 1) * 3) as usize;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_reader(s.as_bytes())
+    }
+}
 
-        &mut self.0[index]
+/// Writes the next generation of the cells from `first` onwards into `out`, returns whether any
+/// of them changed
+fn next_generation(
+    cells: &[Space],
+    neighbours: &Neighbours,
+    rules: &Rules,
+    first: usize,
+    out: &mut [Space],
+) -> bool {
+    let mut changed = false;
+
+    for (offset, next) in out.iter_mut().enumerate() {
+        let index = first + offset;
+        let space = cells[index];
+
+        *next = match space {
+            Space::Floor => Space::Floor,
+            _ => {
+                let taken = neighbours
+                    .of(index)
+                    .iter()
+                    .filter(|&&n| cells[n as usize] == Space::Taken)
+                    .count();
+                rules.next(space, taken as u32)
+            }
+        };
+        changed |= *next != space;
     }
+
+    changed
 }
 
 #[cfg(test)]
@@ -331,4 +449,222 @@ mod test {
 
         assert_eq!(seats, 26);
     }
+
+    #[test]
+    fn presets_match_custom_rules() {
+        let input = "\
+            L.LL.LL.LL
+            LLLLLLL.LL
+            L.L.L..L..
+            LLLL.LL.LL
+            L.LL.LL.LL
+            L.LLLLL.LL
+            ..L.L.....
+            LLLLLLLLLL
+            L.LLLLLL.L
+            L.LLLLL.LL\
+        ";
+
+        let mut seating = Seating::from_reader(Cursor::new(input)).unwrap();
+        let rules = Rules {
+            neighbourhood: Neighbourhood::Custom3d(Lattice::Square.directions()),
+            ..Rules::adjacent()
+        };
+
+        assert_eq!(seating.run(&rules), 5);
+        assert_eq!(seating.num_seats(), 37);
+    }
+
+    #[test]
+    fn counts_neighbourhoods() {
+        let seating = Seating::from_reader(Cursor::new("###\n###\n###")).unwrap();
+        let corner_and_centre = |neighbourhood| {
+            let neighbours = Neighbours::new(
+                &neighbourhood,
+                Lattice::Square,
+                Boundary::Bounded,
+                (3, 3, 1),
+                &seating.cells,
+            );
+            (neighbours.of(0).len(), neighbours.of(4).len())
+        };
+
+        assert_eq!(corner_and_centre(Neighbourhood::Moore(1)), (3, 8));
+        assert_eq!(corner_and_centre(Neighbourhood::Moore(2)), (8, 8));
+        assert_eq!(corner_and_centre(Neighbourhood::VonNeumann(1)), (2, 4));
+        assert_eq!(corner_and_centre(Neighbourhood::VonNeumann(2)), (5, 8));
+        assert_eq!(corner_and_centre(Neighbourhood::LineOfSight), (3, 8));
+        assert_eq!(
+            corner_and_centre(Neighbourhood::Custom(vec![(1, 0), (0, 2)])),
+            (2, 1)
+        );
+    }
+
+    #[test]
+    fn custom_thresholds() {
+        // a seat only fills up next to exactly one taken seat, and never empties
+        let mut seating = Seating::from_reader(Cursor::new("#LLL.L")).unwrap();
+        let rules = Rules {
+            neighbourhood: Neighbourhood::VonNeumann(1),
+            birth: 1..2,
+            survival: 0..9,
+        };
+
+        assert_eq!(seating.run(&rules), 3);
+        assert_eq!(seating.num_seats(), 4);
+    }
+
+    #[test]
+    fn line_of_sight_skips_floor() {
+        let seating =
+            Seating::from_reader(Cursor::new("L...L\n.....\n..L..\n.....\n#...L")).unwrap();
+        let neighbours = Neighbours::new(
+            &Neighbourhood::LineOfSight,
+            Lattice::Square,
+            Boundary::Bounded,
+            (5, 5, 1),
+            &seating.cells,
+        );
+
+        assert_eq!(neighbours.of(12), &[0, 4, 20, 24]);
+        assert_eq!(neighbours.of(0), &[4, 20, 12]);
+        assert_eq!(neighbours.of(6), &[] as &[u32]);
+    }
+
+    #[test]
+    fn threads_match_single_threaded() {
+        let input = include_str!("../input/input.txt");
+
+        for rules in [Rules::adjacent(), Rules::line_of_sight()].iter() {
+            let mut single = Seating::from_reader(Cursor::new(input)).unwrap();
+            let mut threaded = single.clone();
+            threaded.set_threads(3);
+
+            while single.step_with(rules) {
+                assert!(threaded.step_with(rules));
+                assert_eq!(threaded, single);
+            }
+            assert!(!threaded.step_with(rules));
+        }
+    }
+
+    #[test]
+    fn unbounded_grows_to_fit_neighbourhood() {
+        let mut seating = Seating::from_reader(Cursor::new("#.\n.L")).unwrap();
+        seating.set_boundary(Boundary::Unbounded);
+        let rules = Rules {
+            neighbourhood: Neighbourhood::Moore(2),
+            ..Rules::adjacent()
+        };
+
+        seating.step_with(&rules);
+        assert_eq!((seating.width(), seating.height()), (6, 6));
+        assert_eq!(seating.origin(), (2, 2, 0));
+        assert_eq!(seating.get(2, 2), Some(Space::Taken));
+        assert_eq!(seating.get(3, 3), Some(Space::Empty));
+
+        seating.step_with(&Rules::adjacent());
+        assert_eq!((seating.width(), seating.height()), (6, 6));
+    }
+
+    #[test]
+    fn boundaries_change_the_outcome() {
+        let input = include_str!("../input/input.txt");
+        let seats = |boundary, rules: &Rules| {
+            let mut seating = Seating::from_reader(Cursor::new(input)).unwrap();
+            seating.set_boundary(boundary);
+            seating.evolve(rules, Some(500));
+            seating.num_seats()
+        };
+
+        for rules in [Rules::adjacent(), Rules::line_of_sight()].iter() {
+            let bounded = seats(Boundary::Bounded, rules);
+
+            assert_eq!(seats(Boundary::Unbounded, rules), bounded);
+            assert_ne!(seats(Boundary::Toroidal, rules), bounded);
+            assert_ne!(seats(Boundary::Reflective, rules), bounded);
+        }
+    }
+
+    #[test]
+    fn rejects_ragged_rows() {
+        assert!(matches!(
+            Seating::from_reader(Cursor::new("LL\nL")),
+            Err(AocError::ParseInputError)
+        ));
+        assert_eq!(
+            Seating::from_reader(Cursor::new("")).unwrap().num_seats(),
+            0
+        );
+    }
+
+    #[test]
+    fn parses_layers() {
+        let seating = Seating::from_reader(Cursor::new("#L\n..\n\nL.\n.#\n\n")).unwrap();
+        assert_eq!(
+            (seating.width(), seating.height(), seating.depth()),
+            (2, 2, 2)
+        );
+        assert_eq!(seating.get_voxel(1, 1, 1), Some(Space::Taken));
+        assert_eq!(seating.get_voxel(0, 0, 2), None);
+        assert_eq!(seating.to_string(), "#L\n..\n\nL.\n.#\n");
+
+        assert!(matches!(
+            Seating::from_reader(Cursor::new("LL\nLL\n\nLL")),
+            Err(AocError::ParseInputError)
+        ));
+    }
+
+    #[test]
+    fn unbounded_grows_across_layers() {
+        let mut cubic = Seating::from_reader(Cursor::new("#")).unwrap();
+        cubic.set_lattice(Lattice::Cubic);
+        cubic.set_boundary(Boundary::Unbounded);
+        cubic.step_with(&Rules::adjacent());
+
+        assert_eq!((cubic.width(), cubic.height(), cubic.depth()), (3, 3, 3));
+        assert_eq!(cubic.origin(), (1, 1, 1));
+        assert_eq!(cubic.get_voxel(1, 1, 1), Some(Space::Taken));
+
+        // layers of a square lattice never see each other, so they don't grow in depth
+        let mut square = Seating::from_reader(Cursor::new("#\n\n#")).unwrap();
+        square.set_boundary(Boundary::Unbounded);
+        square.step_with(&Rules::adjacent());
+        assert_eq!((square.width(), square.height(), square.depth()), (3, 3, 2));
+    }
+
+    #[test]
+    fn lattices_change_the_outcome() {
+        let input = include_str!("../input/input.txt");
+        let stacked = format!("{}\n{}", input, input);
+        let seats = |input: &str, lattice, rules: &Rules| {
+            let mut seating = Seating::from_reader(Cursor::new(input)).unwrap();
+            seating.set_lattice(lattice);
+            seating.evolve(rules, Some(500));
+            seating.num_seats()
+        };
+
+        for rules in [Rules::adjacent(), Rules::line_of_sight()].iter() {
+            let square = seats(input, Lattice::Square, rules);
+
+            // separate layers of a square lattice run the same as one on its own
+            assert_eq!(seats(&stacked, Lattice::Square, rules), square * 2);
+            assert_ne!(seats(input, Lattice::Hex, rules), square);
+            assert_ne!(seats(&stacked, Lattice::Cubic, rules), square * 2);
+        }
+
+        for &lattice in [Lattice::Hex, Lattice::Cubic].iter() {
+            let mut single = Seating::from_reader(Cursor::new(&stacked)).unwrap();
+            single.set_lattice(lattice);
+            let mut threaded = single.clone();
+            threaded.set_threads(4);
+
+            // a cubic lattice flips between full and empty rather than settling down
+            for _ in 0..20 {
+                let changed = single.step_with(&Rules::line_of_sight());
+                assert_eq!(threaded.step_with(&Rules::line_of_sight()), changed);
+                assert_eq!(threaded, single);
+            }
+        }
+    }
 }
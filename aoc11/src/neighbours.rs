@@ -0,0 +1,366 @@
+use crate::{Boundary, Lattice, Neighbourhood, Space};
+
+/// For every seat, the indices of the seats in its neighbourhood. Floor never changes and can
+/// never be taken, so it's left out entirely and the table stays valid for the whole run.
+///
+/// A seat is never its own neighbour by wrapping or reflecting back onto itself, but two offsets
+/// that land on the same seat both count.
+#[derive(Clone, Debug)]
+pub(crate) struct Neighbours {
+    pub neighbourhood: Neighbourhood,
+    pub lattice: Lattice,
+    pub boundary: Boundary,
+    /// Neighbours of cell `i` are `cells[start[i]..start[i + 1]]`
+    start: Vec<usize>,
+    cells: Vec<u32>,
+}
+
+impl Neighbours {
+    /// `size` is the width, height and depth of `grid`
+    pub fn new(
+        neighbourhood: &Neighbourhood,
+        lattice: Lattice,
+        boundary: Boundary,
+        size: (usize, usize, usize),
+        grid: &[Space],
+    ) -> Self {
+        let grid = Grid {
+            size,
+            boundary,
+            cells: grid,
+        };
+        let directions = lattice.directions();
+        let lists = match (neighbourhood.offsets(lattice), boundary) {
+            (Some(offsets), _) => grid.fixed(&offsets),
+            (None, Boundary::Bounded) | (None, Boundary::Unbounded) => {
+                grid.line_of_sight(&directions)
+            }
+            (None, _) => grid.walk_line_of_sight(&directions),
+        };
+
+        let mut start = Vec::with_capacity(grid.cells.len() + 1);
+        let mut cells = Vec::new();
+        start.push(0);
+        for list in lists {
+            cells.extend(list);
+            start.push(cells.len());
+        }
+
+        Self {
+            neighbourhood: neighbourhood.clone(),
+            lattice,
+            boundary,
+            start,
+            cells,
+        }
+    }
+
+    pub fn of(&self, index: usize) -> &[u32] {
+        &self.cells[self.start[index]..self.start[index + 1]]
+    }
+}
+
+type Offset = (i32, i32, i32);
+
+struct Grid<'a> {
+    size: (usize, usize, usize),
+    boundary: Boundary,
+    cells: &'a [Space],
+}
+
+impl Grid<'_> {
+    /// The cell `steps` lots of `offset` away from `index`, if the boundary puts it anywhere
+    fn resolve(&self, index: usize, (dx, dy, dz): Offset, steps: i64) -> Option<usize> {
+        let (width, height, depth) = self.size;
+        let step = |at: usize, d: i32, len: usize| self.axis(at as i64 + d as i64 * steps, len);
+
+        let x = step(index % width, dx, width)?;
+        let y = step(index / width % height, dy, height)?;
+        let z = step(index / (width * height), dz, depth)?;
+
+        Some((z * height + y) * width + x)
+    }
+
+    fn axis(&self, at: i64, len: usize) -> Option<usize> {
+        let len = len as i64;
+
+        match self.boundary {
+            _ if (0..len).contains(&at) => Some(at as usize),
+            Boundary::Bounded | Boundary::Unbounded => None,
+            Boundary::Toroidal => Some(at.rem_euclid(len) as usize),
+            Boundary::Reflective if len == 1 => Some(0),
+            Boundary::Reflective => {
+                let at = at.rem_euclid(2 * (len - 1));
+                Some(if at < len { at } else { 2 * (len - 1) - at } as usize)
+            }
+        }
+    }
+
+    /// How many steps along `offset` before the boundary brings a walk back where it started
+    fn period(&self, (dx, dy, dz): Offset) -> i64 {
+        let (width, height, depth) = self.size;
+        let axis = |d: i32, len: usize| match (d, self.boundary) {
+            (0, _) => 1,
+            (_, Boundary::Reflective) => (2 * (len as i64 - 1)).max(1),
+            _ => len as i64,
+        };
+
+        lcm(lcm(axis(dx, width), axis(dy, height)), axis(dz, depth))
+    }
+
+    fn fixed(&self, offsets: &[Offset]) -> Vec<Vec<u32>> {
+        (0..self.cells.len())
+            .map(|index| {
+                if self.cells[index] == Space::Floor {
+                    return Vec::new();
+                }
+
+                offsets
+                    .iter()
+                    .filter_map(|&d| Some((d, self.resolve(index, d, 1)?)))
+                    .filter(|&(d, n)| {
+                        self.cells[n] != Space::Floor && (n != index || d == (0, 0, 0))
+                    })
+                    .map(|(_, n)| n as u32)
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Walks out from every seat in each direction until it finds a seat, used when the boundary
+    /// bends lines of sight. A walk that comes back round to where it started sees nothing.
+    fn walk_line_of_sight(&self, directions: &[Offset]) -> Vec<Vec<u32>> {
+        (0..self.cells.len())
+            .map(|index| {
+                if self.cells[index] == Space::Floor {
+                    return Vec::new();
+                }
+
+                directions
+                    .iter()
+                    .filter_map(|&d| {
+                        (1..=self.period(d))
+                            .map(|steps| self.resolve(index, d, steps).unwrap())
+                            .take_while(|&n| n != index)
+                            .find(|&n| self.cells[n] != Space::Floor)
+                            .map(|n| n as u32)
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// The first seat in each direction. Rather than walking out from every seat, the first seat
+    /// from each cell is worked out from the cell next to it: directions that look back in reading
+    /// order are filled in going forwards, and the rest going backwards.
+    fn line_of_sight(&self, directions: &[Offset]) -> Vec<Vec<u32>> {
+        let grid = self.cells;
+        let count = directions.len();
+        // first seat from cell `i` in direction `d` is `first[i * count + d]`
+        let mut first = vec![None; grid.len() * count];
+
+        for (d, &direction) in directions.iter().enumerate() {
+            let fill = |first: &mut Vec<Option<u32>>, index: usize| {
+                if let Some(n) = self.resolve(index, direction, 1) {
+                    first[index * count + d] = match grid[n] {
+                        Space::Floor => first[n * count + d],
+                        _ => Some(n as u32),
+                    };
+                }
+            };
+
+            let (dx, dy, dz) = direction;
+            if (dz, dy, dx) < (0, 0, 0) {
+                (0..grid.len()).for_each(|index| fill(&mut first, index));
+            } else {
+                (0..grid.len())
+                    .rev()
+                    .for_each(|index| fill(&mut first, index));
+            }
+        }
+
+        first
+            .chunks(count)
+            .zip(grid)
+            .map(|(first, &space)| match space {
+                Space::Floor => Vec::new(),
+                _ => first.iter().flatten().copied().collect(),
+            })
+            .collect()
+    }
+}
+
+fn lcm(a: i64, b: i64) -> i64 {
+    let gcd = |mut a: i64, mut b: i64| {
+        while b != 0 {
+            let t = a % b;
+            a = b;
+            b = t;
+        }
+        a
+    };
+
+    a / gcd(a, b) * b
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::convert::TryFrom;
+
+    fn grid(rows: &str) -> (usize, Vec<Space>) {
+        let width = rows.lines().next().unwrap().len();
+        let cells = rows
+            .lines()
+            .flat_map(|row| row.bytes())
+            .map(|b| Space::try_from(b).unwrap())
+            .collect();
+
+        (width, cells)
+    }
+
+    fn neighbours(rows: &str, neighbourhood: Neighbourhood, boundary: Boundary) -> Neighbours {
+        on_lattice(rows, neighbourhood, Lattice::Square, boundary)
+    }
+
+    fn on_lattice(
+        rows: &str,
+        neighbourhood: Neighbourhood,
+        lattice: Lattice,
+        boundary: Boundary,
+    ) -> Neighbours {
+        let (width, cells) = grid(rows);
+
+        Neighbours::new(
+            &neighbourhood,
+            lattice,
+            boundary,
+            (width, cells.len() / width, 1),
+            &cells,
+        )
+    }
+
+    #[test]
+    fn toroidal_wraps() {
+        let adjacent = neighbours("###\n###\n###", Neighbourhood::Moore(1), Boundary::Toroidal);
+        assert_eq!(adjacent.of(0), &[8, 6, 7, 2, 1, 5, 3, 4]);
+
+        let los = neighbours(
+            "L..\n...\n..L",
+            Neighbourhood::LineOfSight,
+            Boundary::Toroidal,
+        );
+        assert_eq!(los.of(0), &[8, 8]);
+        assert_eq!(los.of(8), &[0, 0]);
+    }
+
+    #[test]
+    fn reflective_mirrors() {
+        let adjacent = neighbours(
+            "###\n###\n###",
+            Neighbourhood::Moore(1),
+            Boundary::Reflective,
+        );
+        assert_eq!(adjacent.of(0), &[4, 3, 4, 1, 1, 4, 3, 4]);
+
+        let los = neighbours("L.L..", Neighbourhood::LineOfSight, Boundary::Reflective);
+        assert_eq!(los.of(2), &[0, 0, 0]);
+        assert_eq!(los.of(0), &[2, 2, 2, 2, 2, 2]);
+    }
+
+    #[test]
+    fn bounded_stops_at_edges() {
+        for &boundary in [Boundary::Bounded, Boundary::Unbounded].iter() {
+            let adjacent = neighbours("###\n###\n###", Neighbourhood::Moore(1), boundary);
+            assert_eq!(adjacent.of(0), &[1, 3, 4]);
+
+            let los = neighbours("L..\n...\n..L", Neighbourhood::LineOfSight, boundary);
+            assert_eq!(los.of(0), &[8]);
+        }
+    }
+
+    #[test]
+    fn hex_has_six_directions() {
+        let full = "###\n###\n###";
+        let adjacent = on_lattice(
+            full,
+            Neighbourhood::Moore(1),
+            Lattice::Hex,
+            Boundary::Bounded,
+        );
+        assert_eq!(adjacent.of(4), &[1, 2, 3, 5, 6, 7]);
+        assert_eq!(adjacent.of(0), &[1, 3]);
+        assert_eq!(adjacent.of(2), &[1, 4, 5]);
+
+        let wider = on_lattice(
+            full,
+            Neighbourhood::Moore(2),
+            Lattice::Hex,
+            Boundary::Bounded,
+        );
+        let von_neumann = on_lattice(
+            full,
+            Neighbourhood::VonNeumann(2),
+            Lattice::Hex,
+            Boundary::Bounded,
+        );
+        assert_eq!(wider.of(0), &[1, 2, 3, 4, 6]);
+        assert_eq!(von_neumann.of(0), wider.of(0));
+
+        let corners = "L.L\n...\nL.L";
+        let los = on_lattice(
+            corners,
+            Neighbourhood::LineOfSight,
+            Lattice::Hex,
+            Boundary::Bounded,
+        );
+        assert_eq!(los.of(0), &[2, 6]);
+        assert_eq!(los.of(2), &[0, 6, 8]);
+
+        let walked = on_lattice(
+            corners,
+            Neighbourhood::LineOfSight,
+            Lattice::Hex,
+            Boundary::Toroidal,
+        );
+        assert_eq!(walked.of(0), &[6, 2, 2, 6]);
+    }
+
+    #[test]
+    fn cubic_reaches_across_layers() {
+        let full = vec![Space::Taken; 27];
+        let cubic = |neighbourhood| {
+            let neighbours = Neighbours::new(
+                &neighbourhood,
+                Lattice::Cubic,
+                Boundary::Bounded,
+                (3, 3, 3),
+                &full,
+            );
+            (neighbours.of(0).len(), neighbours.of(13).len())
+        };
+
+        assert_eq!(cubic(Neighbourhood::Moore(1)), (7, 26));
+        assert_eq!(cubic(Neighbourhood::VonNeumann(1)), (3, 6));
+        assert_eq!(cubic(Neighbourhood::LineOfSight), (7, 26));
+        assert_eq!(cubic(Neighbourhood::Custom(vec![(1, 0)])), (1, 1));
+        assert_eq!(cubic(Neighbourhood::Custom3d(vec![(0, 0, 1)])), (1, 1));
+
+        // opposite corners of a 2x2x2 cube, only diagonal rays join them
+        let mut corners = vec![Space::Floor; 8];
+        corners[0] = Space::Empty;
+        corners[7] = Space::Empty;
+        let los = |boundary| {
+            Neighbours::new(
+                &Neighbourhood::LineOfSight,
+                Lattice::Cubic,
+                boundary,
+                (2, 2, 2),
+                &corners,
+            )
+        };
+        assert_eq!(los(Boundary::Bounded).of(0), &[7]);
+        assert_eq!(los(Boundary::Bounded).of(7), &[0]);
+        assert_eq!(los(Boundary::Toroidal).of(0), &[7; 8]);
+    }
+}
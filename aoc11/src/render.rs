@@ -0,0 +1,310 @@
+use crate::{AocError, Seating, Space};
+use gif::{Encoder, Frame, Repeat};
+use std::borrow::Cow;
+use std::convert::TryFrom;
+use std::fmt;
+use std::io::{self, Write};
+
+/// Colours for each kind of space when drawing a seating as an image
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Palette {
+    pub empty: [u8; 3],
+    pub floor: [u8; 3],
+    pub taken: [u8; 3],
+    /// Colour for seats that changed in the last generation, `None` to draw them like any other
+    pub changed: Option<[u8; 3]>,
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Self {
+            empty: [0x2e, 0x8b, 0x57],
+            floor: [0x20, 0x20, 0x20],
+            taken: [0xdc, 0x14, 0x3c],
+            changed: None,
+        }
+    }
+}
+
+impl Palette {
+    /// Index of each cell into `colours`
+    fn indices<'a>(&self, seating: &'a Seating) -> impl Iterator<Item = u8> + 'a {
+        let highlight = self.changed.is_some();
+
+        seating
+            .cells
+            .iter()
+            .zip(seating.back.iter())
+            .map(move |(&space, &previous)| match space {
+                _ if highlight && space != previous => 3,
+                Space::Empty => 0,
+                Space::Floor => 1,
+                Space::Taken => 2,
+            })
+    }
+
+    fn colours(&self) -> [[u8; 3]; 4] {
+        [
+            self.empty,
+            self.floor,
+            self.taken,
+            self.changed.unwrap_or(self.taken),
+        ]
+    }
+}
+
+impl Space {
+    pub(crate) fn to_byte(self) -> u8 {
+        match self {
+            Space::Empty => b'L',
+            Space::Floor => b'.',
+            Space::Taken => b'#',
+        }
+    }
+}
+
+/// The same format as the puzzle input, one line per row with a blank line between layers, so it
+/// parses back into the same seating
+impl fmt::Display for Seating {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (row, cells) in self.cells.chunks(self.width.max(1)).enumerate() {
+            if row > 0 && row % self.height == 0 {
+                writeln!(f)?;
+            }
+
+            let line: String = cells.iter().map(|space| space.to_byte() as char).collect();
+            writeln!(f, "{}", line)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Seating {
+    /// Writes the seating in the same format as the puzzle input, one line per row
+    pub fn write_text<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        write!(writer, "{}", self)
+    }
+
+    /// Draws the seating as RGB pixels, each cell `scale` pixels square, where a scale of 0 counts
+    /// as 1. Layers are stacked one below the other, and hex cells are drawn in their axial rows
+    /// and columns.
+    pub fn pixels(&self, palette: &Palette, scale: usize) -> Vec<u8> {
+        let scale = scale.max(1);
+        let colours = palette.colours();
+        let indices: Vec<u8> = palette.indices(self).collect();
+        let mut pixels = Vec::with_capacity(indices.len() * scale * scale * 3);
+
+        for row in indices.chunks(self.width.max(1)) {
+            for _ in 0..scale {
+                for &index in row {
+                    for _ in 0..scale {
+                        pixels.extend_from_slice(&colours[index as usize]);
+                    }
+                }
+            }
+        }
+
+        pixels
+    }
+
+    /// Writes the seating as a binary PPM image, a numbered series of these makes an image
+    /// sequence. The scale is the same as for `pixels`.
+    pub fn write_ppm<W: Write>(
+        &self,
+        writer: &mut W,
+        palette: &Palette,
+        scale: usize,
+    ) -> io::Result<()> {
+        let scale = scale.max(1);
+        write!(
+            writer,
+            "P6\n{} {}\n255\n",
+            self.width * scale,
+            self.height * self.depth * scale
+        )?;
+
+        writer.write_all(&self.pixels(palette, scale))
+    }
+}
+
+/// Records generations of a seating as the frames of a looping animated GIF. The size of the
+/// GIF is set by the first frame, an unbounded seating has already grown by then.
+pub struct GifRecorder<W: Write> {
+    writer: Option<W>,
+    encoder: Option<Encoder<W>>,
+    palette: Palette,
+    scale: usize,
+    /// Size of the seating in cells, once the first frame has been recorded
+    size: Option<(usize, usize, usize)>,
+    /// Time each frame is shown for, in hundredths of a second
+    pub delay: u16,
+}
+
+impl<W: Write> GifRecorder<W> {
+    pub fn new(writer: W, palette: Palette, scale: usize) -> Self {
+        Self {
+            writer: Some(writer),
+            encoder: None,
+            palette,
+            scale: scale.max(1),
+            size: None,
+            delay: 10,
+        }
+    }
+
+    fn pixels(&self, cells: usize) -> Result<u16, AocError> {
+        let (width, height, depth) = self.size.unwrap_or((0, 0, 0));
+
+        u16::try_from(cells * self.scale).map_err(|_| AocError::ImageTooLarge {
+            width: width * self.scale,
+            height: height * depth * self.scale,
+        })
+    }
+
+    fn start(&mut self, size: (usize, usize, usize)) -> Result<(), AocError> {
+        self.size = Some(size);
+        let (width, height, depth) = size;
+        let (gif_width, gif_height) = (self.pixels(width)?, self.pixels(height * depth)?);
+
+        let global: Vec<u8> = self.palette.colours().iter().flatten().copied().collect();
+        let writer = self.writer.take().unwrap();
+        let mut encoder = Encoder::new(writer, gif_width, gif_height, &global)?;
+        encoder.set_repeat(Repeat::Infinite)?;
+        self.encoder = Some(encoder);
+
+        Ok(())
+    }
+
+    pub fn frame(&mut self, seating: &Seating) -> Result<(), AocError> {
+        let found = (seating.width, seating.height, seating.depth);
+        match self.size {
+            None => self.start(found)?,
+            Some(expected) if expected != found => {
+                return Err(AocError::SizeMismatch { expected, found })
+            }
+            Some(_) => {}
+        }
+
+        let indices: Vec<u8> = self.palette.indices(seating).collect();
+        let mut buffer = Vec::with_capacity(indices.len() * self.scale * self.scale);
+        for row in indices.chunks(seating.width.max(1)) {
+            for _ in 0..self.scale {
+                for &index in row {
+                    buffer.extend(std::iter::repeat_n(index, self.scale));
+                }
+            }
+        }
+
+        let frame = Frame {
+            width: self.pixels(seating.width)?,
+            height: self.pixels(seating.height * seating.depth)?,
+            delay: self.delay,
+            buffer: Cow::Owned(buffer),
+            ..Frame::default()
+        };
+        self.encoder.as_mut().unwrap().write_frame(&frame)?;
+
+        Ok(())
+    }
+
+    /// Finishes the GIF and hands back the writer. Without any frames the GIF is empty.
+    pub fn finish(mut self) -> Result<W, AocError> {
+        if self.encoder.is_none() {
+            self.start((0, 0, 0))?;
+        }
+
+        Ok(self.encoder.take().unwrap().into_inner()?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Rules;
+    use std::io::Cursor;
+
+    fn seating() -> Seating {
+        Seating::from_reader(Cursor::new("L.L\nLLL")).unwrap()
+    }
+
+    #[test]
+    fn writes_text_frames() {
+        let mut seating = seating();
+        let mut frames = Vec::new();
+
+        seating.write_text(&mut frames).unwrap();
+        seating.step_with(&Rules::adjacent());
+        seating.write_text(&mut frames).unwrap();
+
+        assert_eq!(String::from_utf8(frames).unwrap(), "L.L\nLLL\n#.#\n###\n");
+    }
+
+    #[test]
+    fn highlights_changed_cells() {
+        let mut seating = seating();
+        seating.step_with(&Rules::adjacent());
+
+        let palette = Palette {
+            changed: Some([1, 2, 3]),
+            ..Palette::default()
+        };
+        let pixels = seating.pixels(&palette, 2);
+
+        assert_eq!(pixels.len(), 6 * 4 * 3);
+        assert_eq!(&pixels[0..6], &[1, 2, 3, 1, 2, 3]);
+        assert_eq!(&pixels[6..9], &palette.floor);
+
+        let plain = seating.pixels(&Palette::default(), 1);
+        assert_eq!(&plain[0..3], &Palette::default().taken);
+    }
+
+    #[test]
+    fn writes_ppm() {
+        let mut out = Vec::new();
+        seating()
+            .write_ppm(&mut out, &Palette::default(), 1)
+            .unwrap();
+
+        assert!(out.starts_with(b"P6\n3 2\n255\n"));
+        assert_eq!(out.len(), 11 + 18);
+
+        // a scale of 0 is drawn at 1, the same as a GIF
+        let mut unscaled = Vec::new();
+        seating()
+            .write_ppm(&mut unscaled, &Palette::default(), 0)
+            .unwrap();
+        assert_eq!(unscaled, out);
+    }
+
+    #[test]
+    fn records_gif() {
+        let mut seating = seating();
+        let mut recorder = GifRecorder::new(Vec::new(), Palette::default(), 4);
+
+        seating.evolve_with(&Rules::adjacent(), None, |_, seating| {
+            recorder.frame(seating).unwrap()
+        });
+        let gif = recorder.finish().unwrap();
+
+        assert!(gif.starts_with(b"GIF89a"));
+        assert_eq!(&gif[6..10], &[12, 0, 8, 0]);
+        assert_eq!(gif.last(), Some(&0x3b));
+
+        let mut recorder = GifRecorder::new(Vec::new(), Palette::default(), 1);
+        recorder.frame(&seating).unwrap();
+        assert!(matches!(
+            recorder.frame(&Seating::from_reader(Cursor::new("L")).unwrap()),
+            Err(AocError::SizeMismatch {
+                expected: (3, 2, 1),
+                found: (1, 1, 1)
+            })
+        ));
+
+        let wide = Seating::from_reader(Cursor::new("L".repeat(70_000))).unwrap();
+        assert!(matches!(
+            GifRecorder::new(Vec::new(), Palette::default(), 1).frame(&wide),
+            Err(AocError::ImageTooLarge { .. })
+        ));
+    }
+}
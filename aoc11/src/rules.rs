@@ -0,0 +1,203 @@
+use crate::{AocError, Space};
+use std::fmt;
+use std::ops::Range;
+use std::str::FromStr;
+
+/// Which positions around a seat count towards its number of taken neighbours
+#[derive(Clone, Debug, PartialEq)]
+pub enum Neighbourhood {
+    /// Every position within `radius` steps in any direction, diagonals included
+    Moore(usize),
+    /// Every position within a Manhattan distance of `radius`. On a hex lattice this is the same
+    /// as `Moore`.
+    VonNeumann(usize),
+    /// The first seat visible in each of the lattice's directions, looking past any floor
+    LineOfSight,
+    /// Any fixed set of `(x, y)` offsets within a layer
+    Custom(Vec<(i32, i32)>),
+    /// Any fixed set of `(x, y, z)` offsets, which can reach into other layers
+    Custom3d(Vec<(i32, i32, i32)>),
+}
+
+/// How cells are arranged, and so which cells are next to each other. A seating can have several
+/// layers, stacked along z.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Lattice {
+    /// Square cells with eight neighbours, each layer is separate
+    #[default]
+    Square,
+    /// Hexagonal cells in axial coordinates, with x and y as q and r. Each cell has six
+    /// neighbours: left and right, up and up-right, down and down-left. Each layer is separate.
+    Hex,
+    /// Cubic voxels with 26 neighbours, across layers as well as within them
+    Cubic,
+}
+
+/// What lies beyond the edges of the seating area
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Boundary {
+    /// Floor all the way out
+    #[default]
+    Bounded,
+    /// Each edge wraps round to the opposite one
+    Toroidal,
+    /// The seating is mirrored at each edge without repeating the edge itself, so one step past
+    /// the left edge is the second column. Lines of sight bounce off the edges.
+    Reflective,
+    /// The seating area grows with floor as needed, so that every seat has its whole
+    /// neighbourhood inside it
+    Unbounded,
+}
+
+impl fmt::Display for Boundary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Bounded => "bounded",
+            Self::Toroidal => "toroidal",
+            Self::Reflective => "reflective",
+            Self::Unbounded => "unbounded",
+        };
+
+        f.write_str(name)
+    }
+}
+
+impl FromStr for Boundary {
+    type Err = AocError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "bounded" => Ok(Self::Bounded),
+            "toroidal" => Ok(Self::Toroidal),
+            "reflective" => Ok(Self::Reflective),
+            "unbounded" => Ok(Self::Unbounded),
+            _ => Err(AocError::ParseInputError),
+        }
+    }
+}
+
+impl fmt::Display for Lattice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Square => "square",
+            Self::Hex => "hex",
+            Self::Cubic => "cubic",
+        };
+
+        f.write_str(name)
+    }
+}
+
+impl FromStr for Lattice {
+    type Err = AocError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "square" => Ok(Self::Square),
+            "hex" => Ok(Self::Hex),
+            "cubic" => Ok(Self::Cubic),
+            _ => Err(AocError::ParseInputError),
+        }
+    }
+}
+
+impl Lattice {
+    /// The directions lines of sight are drawn in, which are also the cells next to a seat. Ordered
+    /// by z, then y, then x, so the first half look back in reading order.
+    pub fn directions(self) -> Vec<(i32, i32, i32)> {
+        self.within(1, false)
+    }
+
+    /// Every offset within `radius` of a cell, ordered like `directions`. The distance is the
+    /// largest step along any axis, or the sum of them when `manhattan` is set. Hex cells only
+    /// have the one distance, the number of steps between neighbours.
+    fn within(self, radius: usize, manhattan: bool) -> Vec<(i32, i32, i32)> {
+        let radius = radius as i32;
+        let layers = match self {
+            Self::Cubic => radius,
+            _ => 0,
+        };
+
+        let distance = |x: i32, y: i32, z: i32| match self {
+            Self::Hex => (x.abs() + y.abs() + (x + y).abs()) / 2,
+            _ if manhattan => x.abs() + y.abs() + z.abs(),
+            _ => x.abs().max(y.abs()).max(z.abs()),
+        };
+
+        (-layers..=layers)
+            .flat_map(|z| (-radius..=radius).map(move |y| (y, z)))
+            .flat_map(|(y, z)| (-radius..=radius).map(move |x| (x, y, z)))
+            .filter(|&(x, y, z)| (x, y, z) != (0, 0, 0) && distance(x, y, z) <= radius)
+            .collect()
+    }
+}
+
+impl Neighbourhood {
+    /// How far the neighbourhood reaches from a seat along each axis, line of sight reaches as
+    /// far as the cells next to it
+    pub(crate) fn reach(&self, lattice: Lattice) -> (usize, usize, usize) {
+        let offsets = self
+            .offsets(lattice)
+            .unwrap_or_else(|| lattice.directions());
+
+        offsets.iter().fold((0, 0, 0), |(rx, ry, rz), &(x, y, z)| {
+            (
+                rx.max(x.unsigned_abs() as usize),
+                ry.max(y.unsigned_abs() as usize),
+                rz.max(z.unsigned_abs() as usize),
+            )
+        })
+    }
+
+    /// The offsets making up a fixed neighbourhood on a lattice, ordered like
+    /// `Lattice::directions`. `None` for line of sight, where they depend on the floor plan.
+    pub(crate) fn offsets(&self, lattice: Lattice) -> Option<Vec<(i32, i32, i32)>> {
+        match self {
+            Self::Moore(radius) => Some(lattice.within(*radius, false)),
+            Self::VonNeumann(radius) => Some(lattice.within(*radius, true)),
+            Self::LineOfSight => None,
+            Self::Custom(offsets) => Some(offsets.iter().map(|&(x, y)| (x, y, 0)).collect()),
+            Self::Custom3d(offsets) => Some(offsets.clone()),
+        }
+    }
+}
+
+/// How seats change from one generation to the next. Floor never changes, an empty seat is
+/// taken when its count of taken neighbours is within `birth`, and a taken seat stays taken while
+/// its count is within `survival`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Rules {
+    pub neighbourhood: Neighbourhood,
+    pub birth: Range<u32>,
+    pub survival: Range<u32>,
+}
+
+impl Rules {
+    /// Part 1: the adjacent positions, eight of them on a square lattice, a seat is emptied once 4
+    /// of them are taken
+    pub fn adjacent() -> Self {
+        Self {
+            neighbourhood: Neighbourhood::Moore(1),
+            birth: 0..1,
+            survival: 0..4,
+        }
+    }
+
+    /// Part 2: the first seat in each direction, a seat is emptied once 5 of them are taken
+    pub fn line_of_sight() -> Self {
+        Self {
+            neighbourhood: Neighbourhood::LineOfSight,
+            birth: 0..1,
+            survival: 0..5,
+        }
+    }
+
+    /// What `space` becomes with `taken` taken neighbours
+    pub fn next(&self, space: Space, taken: u32) -> Space {
+        match space {
+            Space::Empty if self.birth.contains(&taken) => Space::Taken,
+            Space::Taken if !self.survival.contains(&taken) => Space::Empty,
+            space => space,
+        }
+    }
+}